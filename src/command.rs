@@ -4,255 +4,374 @@ use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
 use clap::Parser;
-use full_moon::ast::LastStmt;
 use log::error;
 use log::info;
-use log::warn;
 
-use crate::link_mutator::*;
-use crate::require_parser::*;
+use console::style;
+
+use crate::diff::*;
+use crate::fixer::*;
+use crate::package_types::*;
+use crate::project::*;
+use crate::report::*;
+use crate::revert::*;
 use crate::sourcemap::*;
 
 #[derive(Parser, Debug)]
-#[clap(author, version, about)]
+#[clap(author, version, about, args_conflicts_with_subcommands = true)]
 pub struct Command {
-    /// Path to sourcemap
+    #[clap(subcommand)]
+    pub subcommand: Option<Subcommand>,
+
+    /// Path to sourcemap. Without one, the files linked by link files are inferred from the wally `_Index` layout
     #[clap(short, long, value_parser)]
-    pub sourcemap: PathBuf,
+    pub sourcemap: Option<PathBuf>,
+
+    /// Rojo project file (e.g. default.project.json) to build the sourcemap from, instead of --sourcemap
+    #[clap(long, value_parser, conflicts_with = "sourcemap")]
+    pub project: Option<PathBuf>,
+
+    /// Directory the file paths in the sourcemap are relative to. Defaults to the directory of the sourcemap
+    #[clap(long, value_parser, conflicts_with = "project")]
+    pub project_root: Option<PathBuf>,
 
     /// Path to packages
     #[clap(value_parser)]
     pub packages_folders: Vec<PathBuf>,
-}
 
-fn find_node(root: &SourcemapNode, path: PathBuf) -> Option<Vec<&SourcemapNode>> {
-    let mut stack = vec![vec![root]];
+    /// Check that all link files are up to date without writing them, failing if any are stale
+    #[clap(long)]
+    pub check: bool,
 
-    while let Some(node_path) = stack.pop() {
-        let node = node_path.last().unwrap();
-        if node.file_paths.contains(&path.to_path_buf()) {
-            return Some(node_path);
-        }
+    /// Print a diff of every link file change instead of writing it. Printed to stderr when the report is printed to
+    /// stdout
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Number of link files to handle concurrently
+    #[clap(short, long, value_parser, default_value_t = 1)]
+    pub jobs: usize,
+
+    /// Keep running, fixing link files again whenever the sourcemap or packages change
+    #[clap(short, long, conflicts_with = "check")]
+    pub watch: bool,
+
+    /// Format of a report with one record per link file, printed to stdout unless a report file is given
+    #[clap(long, value_enum, conflicts_with = "watch")]
+    pub report_format: Option<ReportFormat>,
+
+    /// Path to write the report to. Defaults to a JSON report
+    #[clap(long, value_parser, conflicts_with = "watch")]
+    pub report_file: Option<PathBuf>,
+
+    /// Write the re-exported types of each package to `<TYPES_DIR>/<Name>.luau` instead of changing link files,
+    /// adding a `.luaurc` alias for the directory
+    #[clap(long, value_parser)]
+    pub types_dir: Option<PathBuf>,
+
+    /// Write a module re-exporting the types of every direct dependency under a namespace, e.g. `Packages/Types.luau`
+    #[clap(long, value_parser)]
+    pub types_module: Option<PathBuf>,
+
+    /// Naming of the types in the types module, where `{package}` and `{type}` are replaced with the package and type names
+    #[clap(long, default_value = DEFAULT_TYPES_MODULE_NAMING)]
+    pub types_module_naming: String,
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum Subcommand {
+    /// Restore link files generated by this tool to the plain `return require(...)` form generated by wally
+    Revert {
+        /// Path to packages
+        #[clap(value_parser)]
+        packages_folders: Vec<PathBuf>,
+
+        /// Print a diff of every link file change instead of writing it. Printed to stderr when the report is printed to
+        /// stdout
+        #[clap(long)]
+        dry_run: bool,
+    },
+}
 
-        for child in &node.children {
-            let mut path = node_path.clone();
-            path.push(child);
-            stack.push(path);
+/// Renders a unified diff of a link file change, followed by a summary of the changed type exports
+fn link_diff(path: &Path, old_contents: &str, new_contents: &str) -> String {
+    let diff = unified_diff(&path.display().to_string(), old_contents, new_contents);
+
+    let lines = diff_lines(old_contents, new_contents);
+    let (mut added, mut removed) = (0, 0);
+    for line in lines {
+        match line {
+            DiffLine::Added(text) if text.trim_start().starts_with("export type") => added += 1,
+            DiffLine::Removed(text) if text.trim_start().starts_with("export type") => removed += 1,
+            _ => {}
         }
     }
 
-    None
+    let package_name = path.file_stem().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().to_string(),
+    );
+    format!(
+        "{diff}{}: {} {} export type lines\n\n",
+        style(package_name).bold(),
+        style(format!("+{added}")).green(),
+        style(format!("-{removed}")).red(),
+    )
 }
 
-fn lua_files_filter(path: &&PathBuf) -> bool {
-    match path.extension() {
-        Some(extension) => extension == "lua" || extension == "luau",
-        None => false,
+/// Prints a diff of every change to a link file or its type declaration file
+fn print_link_changes(results: &[ThunkResult], to_stderr: bool) {
+    for result in results {
+        if let Some(change) = &result.change {
+            print_diff(
+                &link_diff(&change.path, &change.old_contents, &change.new_contents),
+                to_stderr,
+            );
+        }
     }
 }
 
-/// Given a list of components (e.g., ['script', 'Parent', 'Example']), converts it to a file path
-fn file_path_from_components(
-    path: &Path,
-    root: &SourcemapNode,
-    path_components: Vec<String>,
-) -> Result<PathBuf> {
-    let mut iter = path_components.iter();
-    let first_in_chain = iter.next().context("No path components")?;
-
-    if !(first_in_chain == "script" || first_in_chain == "game") {
-        bail!("require expression does not start with 'script' or 'game', cannot determine starting point");
+fn print_diff(diff: &str, to_stderr: bool) {
+    if to_stderr {
+        eprint!("{diff}");
+    } else {
+        print!("{diff}");
     }
+}
 
-    let mut node_path = if first_in_chain == "script" {
-        find_node(root, path.canonicalize()?)
-            .with_context(|| format!("Linker node '{}' not found in sourcemap", path.display()))?
-    } else {
-        vec![root]
-    };
-
-    for component in iter {
-        if component == "Parent" {
-            node_path
-                .pop()
-                .context("No parent found in linked components")?;
-        } else {
-            node_path.push(
-                node_path
-                    .last()
-                    .unwrap()
-                    .find_child(component.to_string())
-                    .with_context(|| {
-                        format!(
-                            "Child '{component}' not found in '{}'",
-                            node_path
-                                .iter()
-                                .map(|node| node.name.as_str())
-                                .collect::<Vec<_>>()
-                                .join("/")
-                        )
-                    })?,
-            );
+impl Command {
+    pub(crate) fn options(&self) -> Options {
+        Options {
+            check: self.check,
+            dry_run: self.dry_run,
+            jobs: self.jobs,
+            types_dir: self.types_dir.clone(),
+            types_module: self.types_module.clone(),
         }
     }
 
-    let current = node_path.last().unwrap();
-    let file_path = current
-        .file_paths
-        .iter()
-        .find(lua_files_filter)
-        .context("No .lua/.luau file found for linked node")?
-        .clone();
-    info!(
-        "Link require points to {} [{}] @ '{}'",
-        current.name,
-        current.class_name,
-        file_path.display()
-    );
-
-    Ok(file_path)
-}
+    /// The file the sourcemap is read or built from, if any
+    pub(crate) fn sourcemap_file(&self) -> Option<&Path> {
+        self.sourcemap.as_deref().or(self.project.as_deref())
+    }
 
-enum MutateResult {
-    Successful,
-    FailedToParseReturnStmt,
-}
+    /// Reads the sourcemap, or builds it from the project, with all file paths normalized.
+    /// Returns `None` if neither was given
+    pub(crate) fn load_sourcemap(&self) -> Result<Option<SourcemapNode>> {
+        if let Some(project) = &self.project {
+            let mut sourcemap = sourcemap_from_project(project)?;
+            mutate_sourcemap(&mut sourcemap, Path::new("."))?;
+            return Ok(Some(sourcemap));
+        }
 
-fn mutate_thunk(path: &Path, root: &SourcemapNode) -> Result<MutateResult> {
-    info!("Found link file '{}'", path.display());
-
-    // The entry should be a thunk
-    let parsed_code = match full_moon::parse(&std::fs::read_to_string(path)?) {
-        Ok(parsed_code) => parsed_code,
-        Err(errors) => bail!(errors
-            .iter()
-            .map(|err| err.to_string())
-            .collect::<Vec<_>>()
-            .join("\n")),
-    };
-
-    if let Some(LastStmt::Return(r#return)) = parsed_code.nodes().last_stmt() {
-        let returned_expression = r#return.returns().iter().next().unwrap();
-
-        let path_components = match match_require(returned_expression) {
-            Ok(components) => components,
-            Err(err) => {
-                warn!("Malformed link file, could not parse return expression, skipping. Run `wally install` to regenerate link files");
-                error!("{:#}", err);
-                return Ok(MutateResult::FailedToParseReturnStmt);
-            }
+        let Some(sourcemap_path) = &self.sourcemap else {
+            return Ok(None);
         };
+        let sourcemap_contents =
+            std::fs::read_to_string(sourcemap_path).context("Failed to read sourcemap file")?;
+        let mut sourcemap: SourcemapNode =
+            serde_json::from_str(&sourcemap_contents).context("Failed to parse sourcemap file")?;
 
-        info!(
-            "Require expression converted to path: '{}'",
-            path_components.join("/")
-        );
-
-        let file_path = file_path_from_components(path, root, path_components)
-            .context("Could not convert require expression to file path")?;
-        let pass_through_contents =
-            std::fs::read_to_string(file_path).context("Failed to read linked file")?;
-        let returns = r#return.returns().clone();
-        let new_link_contents = mutate_link(parsed_code, returns, &pass_through_contents)
-            .context("Failed to create new link contents")?;
-
-        match new_link_contents {
-            MutateLinkResult::Changed(new_ast) => {
-                info!("Exported types found, writing new linker file");
-                std::fs::write(path, new_ast.to_string())?
-            }
-            MutateLinkResult::Unchanged => {
-                info!("No exported types, leaving unchanged");
-            }
+        // Mutate the sourcemap so that all file paths are absolute and normalized for simplicity
+        let project_root = match &self.project_root {
+            Some(project_root) => project_root.as_path(),
+            None => match sourcemap_path.parent() {
+                Some(parent) if parent != Path::new("") => parent,
+                _ => Path::new("."),
+            },
         };
-    } else {
-        warn!("Malformed link file, no return statement found, skipping. Run `wally install` to regenerate link files");
-        return Ok(MutateResult::FailedToParseReturnStmt);
-    }
+        mutate_sourcemap(&mut sourcemap, project_root)?;
 
-    Ok(MutateResult::Successful)
-}
+        Ok(Some(sourcemap))
+    }
 
-// Mutate thunk with error handled, to allow continuing
-fn handled_mutate_thunk(path: &Path, root: &SourcemapNode) -> bool {
-    match mutate_thunk(path, root) {
-        Ok(result) => matches!(result, MutateResult::Successful),
-        Err(err) => {
-            error!("{:#}", err);
-            false
+    /// Creates a fixer from the loaded sourcemap, or one inferring links from the `_Index` layout without it
+    pub(crate) fn fixer<'a>(&self, sourcemap: Option<&'a SourcemapNode>) -> Fixer<'a> {
+        match sourcemap {
+            Some(sourcemap) => Fixer::new(sourcemap, self.options()),
+            None => Fixer::without_sourcemap(self.options()),
         }
     }
-}
 
-fn handle_index_directory(path: &Path, root: &SourcemapNode) -> Result<bool> {
-    let mut success = true;
-    for package_entry in std::fs::read_dir(path)?.flatten() {
-        for thunk in std::fs::read_dir(package_entry.path())?.flatten() {
-            if thunk.file_type().unwrap().is_file() {
-                success &= handled_mutate_thunk(&thunk.path(), root);
-            }
+    /// Whether the report is printed to stdout, in which case diffs are printed to stderr so they do not mix
+    fn report_to_stdout(&self) -> bool {
+        self.report_format.is_some() && self.report_file.is_none()
+    }
+
+    /// Prints a diff of every link file change, when dry running
+    pub(crate) fn print_changes(&self, results: &[ThunkResult]) {
+        if self.dry_run {
+            print_link_changes(results, self.report_to_stdout());
         }
     }
 
-    Ok(success)
-}
+    /// Fixes every packages folder, returning their results
+    pub(crate) fn handle_packages_folders(
+        &self,
+        fixer: &Fixer,
+    ) -> Result<Vec<PackagesFolderResult>> {
+        let mut results = Vec::new();
+
+        if let Some(types_dir) = &self.types_dir {
+            check_types_file_collisions(types_dir, &self.packages_folders)?;
+            if !self.check && !self.dry_run && write_luaurc_alias(types_dir)? {
+                info!("Added an alias for '{}' to .luaurc", types_dir.display());
+            }
+        }
 
-fn handle_packages_folder(path: &Path, sourcemap: &SourcemapNode) -> Result<bool> {
-    let mut success = true;
-
-    for entry in std::fs::read_dir(path)
-        .context("Failed to read packages folder")?
-        .flatten()
-    {
-        if entry.file_name() == "_Index" {
-            match handle_index_directory(&entry.path(), sourcemap) {
-                Ok(index_success) => success &= index_success,
-                Err(err) => {
-                    error!("{:#}", err);
-                    success = false;
+        for path in &self.packages_folders {
+            let folder_result = fixer.fix_packages_folder(path)?;
+            self.print_changes(&folder_result.thunks);
+
+            if folder_result.is_success() {
+                if self.check {
+                    info!("Link files are up to date for path '{}'", path.display());
+                } else {
+                    info!(
+                        "Mutation completed successfully for path '{}'",
+                        path.display()
+                    );
                 }
+            } else if self.check {
+                error!("Check failed for path '{}'", path.display());
+            } else {
+                error!("Mutation failed for path '{}'", path.display());
             }
-            continue;
+
+            results.push(folder_result);
         }
 
-        success &= handled_mutate_thunk(&entry.path(), sourcemap)
+        let thunks: Vec<&ThunkResult> = results.iter().flat_map(|result| &result.thunks).collect();
+        self.update_types_module(&thunks)?;
+
+        Ok(results)
     }
 
-    Ok(success)
-}
+    /// Brings the aggregated types module up to date with the results of all link files
+    pub(crate) fn update_types_module(&self, thunks: &[&ThunkResult]) -> Result<()> {
+        let Some(types_module) = &self.types_module else {
+            return Ok(());
+        };
 
-impl Command {
-    pub fn run(&self) -> Result<()> {
-        let sourcemap_contents =
-            std::fs::read_to_string(&self.sourcemap).context("Failed to read sourcemap file")?;
-        let mut sourcemap: SourcemapNode =
-            serde_json::from_str(&sourcemap_contents).context("Failed to parse sourcemap file")?;
+        let new_contents = types_module_contents(
+            types_module,
+            thunks,
+            &self.types_module_naming,
+            self.types_dir.as_deref(),
+        )
+        .context("Failed to create types module")?;
+        let old_contents = std::fs::read_to_string(types_module).unwrap_or_default();
+
+        if new_contents == old_contents {
+            info!("Types module '{}' is up to date", types_module.display());
+        } else if self.check {
+            bail!("Types module '{}' is out of date", types_module.display());
+        } else if self.dry_run {
+            print_diff(
+                &link_diff(types_module, &old_contents, &new_contents),
+                self.report_to_stdout(),
+            );
+        } else {
+            std::fs::write(types_module, new_contents).context("Failed to write types module")?;
+            info!("Wrote types module '{}'", types_module.display());
+        }
+
+        Ok(())
+    }
 
-        // Mutate the sourcemap so that all file paths are canonicalized for simplicity
-        // And that they contain pointers to their parent
-        mutate_sourcemap(&mut sourcemap)?;
+    /// Writes a report of the run to the report file, or stdout if there is none
+    fn write_report(&self, results: &[PackagesFolderResult]) -> Result<()> {
+        if self.report_format.is_none() && self.report_file.is_none() {
+            return Ok(());
+        }
 
-        let mut failures = 0;
+        let report = render_report(self.report_format.unwrap_or(ReportFormat::Json), results);
+        match &self.report_file {
+            Some(path) => std::fs::write(path, report).context("Failed to write report file")?,
+            None => println!("{report}"),
+        }
+
+        Ok(())
+    }
+
+    /// Reports the final outcome of a run, failing if any packages folder failed
+    pub(crate) fn summarize(&self, results: &[PackagesFolderResult]) -> Result<()> {
+        let failures = results.iter().filter(|result| !result.is_success()).count();
         let total = self.packages_folders.len();
 
-        for path in &self.packages_folders {
-            if handle_packages_folder(path, &sourcemap)? {
+        if self.check {
+            if failures == 0 {
+                info!("All link files are up to date");
+            } else {
+                bail!(
+                    "Link files are out of date or malformed in {} out of {} paths. Run `wally-package-types` to regenerate them",
+                    failures,
+                    total
+                );
+            }
+        } else if failures == 0 {
+            info!("Mutation completed successfully for all paths");
+        } else {
+            bail!("Mutation failed for {} out of {} paths", failures, total);
+        }
+
+        Ok(())
+    }
+
+    /// Restores the generated link files in every packages folder
+    fn revert(&self, packages_folders: &[PathBuf], dry_run: bool) -> Result<()> {
+        let mut failures = 0;
+
+        for path in packages_folders {
+            let folder_result = revert_packages_folder(path, dry_run)?;
+            if dry_run {
+                print_link_changes(&folder_result.thunks, false);
+            }
+
+            if folder_result.is_success() {
                 info!(
-                    "Mutation completed successfully for path '{}'",
+                    "Revert completed successfully for path '{}'",
                     path.display()
                 );
             } else {
+                error!("Revert failed for path '{}'", path.display());
                 failures += 1;
-                error!("Mutation failed for path '{}'", path.display());
             }
         }
 
-        if failures == 0 {
-            info!("Mutation completed successfully for all paths");
-        } else {
-            bail!("Mutation failed for {} out of {} paths", failures, total);
+        if failures > 0 {
+            bail!(
+                "Revert failed for {} out of {} paths",
+                failures,
+                packages_folders.len()
+            );
         }
 
+        info!("Revert completed successfully for all paths");
         Ok(())
     }
+
+    pub fn run(&self) -> Result<()> {
+        if let Some(Subcommand::Revert {
+            packages_folders,
+            dry_run,
+        }) = &self.subcommand
+        {
+            return self.revert(packages_folders, *dry_run);
+        }
+
+        if self.watch {
+            return self.watch();
+        }
+
+        let sourcemap = self.load_sourcemap()?;
+        if sourcemap.is_none() {
+            info!("No sourcemap or project given, inferring links from the _Index layout");
+        }
+        let fixer = self.fixer(sourcemap.as_ref());
+
+        let results = self.handle_packages_folders(&fixer)?;
+        self.write_report(&results)?;
+        self.summarize(&results)
+    }
 }
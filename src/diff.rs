@@ -0,0 +1,182 @@
+use std::ops::Range;
+
+use console::style;
+
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DiffLine<'a> {
+    Context(&'a str),
+    Added(&'a str),
+    Removed(&'a str),
+}
+
+impl DiffLine<'_> {
+    fn is_change(&self) -> bool {
+        !matches!(self, DiffLine::Context(_))
+    }
+}
+
+/// Computes a line-based diff between two strings using the longest common subsequence
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old_lines = old.lines().collect::<Vec<_>>();
+    let new_lines = new.lines().collect::<Vec<_>>();
+
+    // lcs[i][j] holds the length of the longest common subsequence of old_lines[i..] and new_lines[j..]
+    let mut lcs = vec![vec![0usize; new_lines.len() + 1]; old_lines.len() + 1];
+    for i in (0..old_lines.len()).rev() {
+        for j in (0..new_lines.len()).rev() {
+            lcs[i][j] = if old_lines[i] == new_lines[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old_lines.len() && j < new_lines.len() {
+        if old_lines[i] == new_lines[j] {
+            lines.push(DiffLine::Context(old_lines[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            lines.push(DiffLine::Removed(old_lines[i]));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new_lines[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old_lines[i..].iter().map(|line| DiffLine::Removed(line)));
+    lines.extend(new_lines[j..].iter().map(|line| DiffLine::Added(line)));
+
+    lines
+}
+
+/// Groups a diff into hunks of changed lines surrounded by up to `CONTEXT_LINES` lines of context
+fn hunks(lines: &[DiffLine]) -> Vec<Range<usize>> {
+    let changes = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.is_change())
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for index in changes {
+        let start = index.saturating_sub(CONTEXT_LINES);
+        let end = (index + CONTEXT_LINES + 1).min(lines.len());
+
+        match ranges.last_mut() {
+            Some(last) if start <= last.end => last.end = end,
+            _ => ranges.push(start..end),
+        }
+    }
+
+    ranges
+}
+
+/// Renders a colored unified diff between the old and new contents of a file
+pub fn unified_diff(name: &str, old: &str, new: &str) -> String {
+    let lines = diff_lines(old, new);
+    let mut output = format!(
+        "{}\n{}\n",
+        style(format!("--- a/{name}")).bold(),
+        style(format!("+++ b/{name}")).bold()
+    );
+
+    let (mut old_line, mut new_line) = (1, 1);
+    let mut position = 0;
+    for range in hunks(&lines) {
+        // Only context lines are skipped between hunks, so both sides advance equally
+        old_line += range.start - position;
+        new_line += range.start - position;
+        position = range.end;
+
+        let hunk = &lines[range];
+        let old_count = hunk
+            .iter()
+            .filter(|line| !matches!(line, DiffLine::Added(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|line| !matches!(line, DiffLine::Removed(_)))
+            .count();
+
+        output.push_str(&format!(
+            "{}\n",
+            style(format!(
+                "@@ -{},{} +{},{} @@",
+                if old_count == 0 {
+                    old_line - 1
+                } else {
+                    old_line
+                },
+                old_count,
+                if new_count == 0 {
+                    new_line - 1
+                } else {
+                    new_line
+                },
+                new_count
+            ))
+            .cyan()
+        ));
+
+        for line in hunk {
+            let rendered = match line {
+                DiffLine::Context(text) => format!(" {text}"),
+                DiffLine::Added(text) => style(format!("+{text}")).green().to_string(),
+                DiffLine::Removed(text) => style(format!("-{text}")).red().to_string(),
+            };
+            output.push_str(&rendered);
+            output.push('\n');
+        }
+
+        old_line += old_count;
+        new_line += new_count;
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_inserted_lines() {
+        assert_eq!(
+            diff_lines("a\nc\n", "a\nb\nc\n"),
+            vec![
+                DiffLine::Context("a"),
+                DiffLine::Added("b"),
+                DiffLine::Context("c")
+            ]
+        );
+    }
+
+    #[test]
+    fn diffs_replaced_lines() {
+        assert_eq!(
+            diff_lines("return require(x)\n", "local M = require(x)\nreturn M\n"),
+            vec![
+                DiffLine::Removed("return require(x)"),
+                DiffLine::Added("local M = require(x)"),
+                DiffLine::Added("return M"),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_distant_changes_into_hunks() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let new = "0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let lines = diff_lines(old, new);
+        let hunks = hunks(&lines);
+
+        assert_eq!(hunks, vec![0..4, 7..11]);
+    }
+}
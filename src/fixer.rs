@@ -0,0 +1,991 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use full_moon::ast::{punctuated::Pair, LastStmt};
+use log::error;
+
+use crate::index_layout::*;
+use crate::link_mutator::*;
+use crate::package_types::*;
+use crate::require_parser::*;
+use crate::sourcemap::*;
+use crate::string_require::*;
+use crate::thunk_log::ThunkLog;
+
+/// The maximum number of links followed when a link points to another link
+const MAX_LINK_CHAIN_DEPTH: usize = 16;
+
+/// Options controlling how link files are fixed. Created with [`Options::default`] and the builder methods, so that
+/// new options can be added without breaking existing code
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Options {
+    /// Only check whether link files are up to date, reporting stale ones as [`ThunkOutcome::OutOfDate`]
+    pub check: bool,
+    /// Compute the new contents of link files without writing them
+    pub dry_run: bool,
+    /// Number of link files to handle concurrently
+    pub jobs: usize,
+    /// Write the re-exported types of every direct dependency to `<types_dir>/<Name>.luau` instead of changing link files
+    pub types_dir: Option<PathBuf>,
+    /// The aggregated types module, which is skipped if it is inside a packages folder since it is not a link file
+    pub types_module: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            check: false,
+            dry_run: false,
+            jobs: 1,
+            types_dir: None,
+            types_module: None,
+        }
+    }
+}
+
+impl Options {
+    /// Only check whether link files are up to date
+    pub fn check(mut self, check: bool) -> Self {
+        self.check = check;
+        self
+    }
+
+    /// Compute the new contents of link files without writing them
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Handle `jobs` link files concurrently
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Write type declaration files to `types_dir` instead of changing link files
+    pub fn types_dir(mut self, types_dir: Option<PathBuf>) -> Self {
+        self.types_dir = types_dir;
+        self
+    }
+
+    /// Skip the aggregated types module when it is inside a packages folder
+    pub fn types_module(mut self, types_module: Option<PathBuf>) -> Self {
+        self.types_module = types_module;
+        self
+    }
+}
+
+/// The outcome of fixing a single link file
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ThunkOutcome {
+    /// The link re-exports the types of the module it points to, either already or after being fixed
+    Successful,
+    /// The module the link points to has no exported types, so the link was left unchanged
+    Unchanged,
+    /// The link is not up to date, only reported when checking
+    OutOfDate,
+    /// The link does not end in a supported `return require(...)` statement
+    FailedToParseReturnStmt,
+    /// The link could not be fixed
+    Error(anyhow::Error),
+}
+
+/// The current and new contents of a link file, or of its type declaration file, which needs to change
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct LinkChange {
+    /// Path to the changed file
+    pub path: PathBuf,
+    /// The contents of the file on disk, empty if it does not exist
+    pub old_contents: String,
+    /// The contents the file should have, empty if it should be removed
+    pub new_contents: String,
+}
+
+/// The result of fixing a single link file
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ThunkResult {
+    /// Path to the link file
+    pub path: PathBuf,
+    /// The path the link requires, if it could be parsed
+    pub require_path: Option<RequirePath>,
+    /// The file the link points to, if it could be resolved
+    pub linked_file: Option<PathBuf>,
+    /// The names of the types re-exported by the link
+    pub exported_types: Vec<String>,
+    /// The change to the link file or its type declaration file, if it was not up to date. Only written when neither
+    /// checking nor dry running
+    pub change: Option<LinkChange>,
+    pub outcome: ThunkOutcome,
+}
+
+impl ThunkResult {
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self.outcome,
+            ThunkOutcome::Successful | ThunkOutcome::Unchanged
+        )
+    }
+}
+
+/// The result of fixing all link files in a packages folder
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct PackagesFolderResult {
+    /// Path to the packages folder
+    pub path: PathBuf,
+    /// Whether all link files in the folder could be found, i.e. its `_Index` could be fully read
+    pub complete: bool,
+    pub thunks: Vec<ThunkResult>,
+}
+
+impl PackagesFolderResult {
+    pub fn is_success(&self) -> bool {
+        self.complete && self.thunks.iter().all(ThunkResult::is_success)
+    }
+}
+
+fn lua_files_filter(path: &&PathBuf) -> bool {
+    match path.extension() {
+        Some(extension) => extension == "lua" || extension == "luau",
+        None => false,
+    }
+}
+
+/// Given a list of components (e.g., ['script', 'Parent', 'Example']), converts it to a file path
+fn file_path_from_components(
+    path: &Path,
+    sourcemap: &SourcemapIndex,
+    path_components: &[PathComponent],
+    log: &mut ThunkLog,
+) -> Result<PathBuf> {
+    let mut iter = path_components.iter();
+    let first_in_chain = iter.next().context("No path components")?;
+
+    if !(*first_in_chain == "script" || *first_in_chain == "game") {
+        bail!("require expression does not start with 'script' or 'game', cannot determine starting point");
+    }
+
+    let mut current = if *first_in_chain == "script" {
+        sourcemap
+            .find_by_path(path)
+            .with_context(|| format!("Linker node '{}' not found in sourcemap", path.display()))?
+    } else {
+        sourcemap.root()
+    };
+
+    for component in iter {
+        current = match component {
+            PathComponent::Child(name) if name == "Parent" => sourcemap
+                .parent(current)
+                .context("No parent found in linked components")?,
+            PathComponent::Child(name) => {
+                sourcemap.find_child(current, name).with_context(|| {
+                    format!(
+                        "Child '{name}' not found in '{}'",
+                        sourcemap.full_name(current)
+                    )
+                })?
+            }
+            PathComponent::Ancestor(name) => {
+                let mut ancestor = sourcemap.parent(current);
+                while let Some(id) = ancestor {
+                    if sourcemap.node(id).name == *name {
+                        break;
+                    }
+                    ancestor = sourcemap.parent(id);
+                }
+                ancestor
+                    .with_context(|| format!("Ancestor '{name}' not found in linked components"))?
+            }
+        };
+    }
+
+    let full_name = sourcemap.full_name(current);
+    let current = sourcemap.node(current);
+    let mut lua_files = current
+        .file_paths
+        .iter()
+        .filter(lua_files_filter)
+        .peekable();
+    if lua_files.peek().is_none() {
+        bail!("No .lua/.luau file found for linked node '{full_name}'");
+    }
+
+    // Sourcemap paths are only resolved here, so files removed since it was generated only fail the link files
+    // needing them
+    let file_path = lua_files
+        .find_map(|file_path| match file_path.canonicalize() {
+            Ok(file_path) => Some(file_path),
+            Err(_) => {
+                log.warn(format!(
+                    "File '{}' of sourcemap node '{full_name}' no longer exists, the sourcemap may be out of date",
+                    file_path.display()
+                ));
+                None
+            }
+        })
+        .with_context(|| {
+            format!("File of linked node '{full_name}' not found. If it was removed, regenerate the sourcemap")
+        })?;
+    log.info(format!(
+        "Link require points to {} [{}] @ '{}'",
+        current.name,
+        current.class_name,
+        file_path.display()
+    ));
+
+    Ok(file_path)
+}
+
+/// Writes a file through a temporary file next to it, so that other workers following links through it never read
+/// it half-written
+fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut temporary_name = std::ffi::OsString::from(".");
+    temporary_name.push(path.file_name().unwrap_or_default());
+    temporary_name.push(format!(".{}.tmp", std::process::id()));
+    let temporary_path = path.with_file_name(temporary_name);
+
+    std::fs::write(&temporary_path, contents)
+        .and_then(|_| std::fs::rename(&temporary_path, path))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&temporary_path);
+        })
+}
+
+/// The module a link file points to, after following any links
+struct LinkedModule {
+    path: PathBuf,
+    contents: String,
+    /// The require paths of the links followed from the module required by the link file to reach this module
+    hops: Vec<RequirePath>,
+}
+
+/// Fixes wally link files (thunks) so that they re-export the types of the modules they point to
+pub struct Fixer<'a> {
+    sourcemap: Option<SourcemapIndex<'a>>,
+    options: Options,
+}
+
+impl<'a> Fixer<'a> {
+    /// Creates a fixer from a sourcemap whose file paths have been normalized with [`mutate_sourcemap`]
+    pub fn new(sourcemap: &'a SourcemapNode, options: Options) -> Self {
+        // Index the sourcemap so that nodes can be found by path and contain pointers to their parent
+        Fixer {
+            sourcemap: Some(SourcemapIndex::new(sourcemap)),
+            options,
+        }
+    }
+
+    /// Creates a fixer without a sourcemap, which infers the files linked by instance requires from the wally
+    /// `_Index` layout instead
+    pub fn without_sourcemap(options: Options) -> Self {
+        Fixer {
+            sourcemap: None,
+            options,
+        }
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Converts the path required by the file at `path` into the path of the required file
+    fn resolve_require(
+        &self,
+        path: &Path,
+        require_path: &RequirePath,
+        log: &mut ThunkLog,
+    ) -> Result<PathBuf> {
+        match require_path {
+            RequirePath::Instance(path_components) => match &self.sourcemap {
+                Some(sourcemap) => file_path_from_components(path, sourcemap, path_components, log),
+                None => file_path_from_index_layout(path, path_components)
+                    .and_then(|file_path| {
+                        file_path
+                            .canonicalize()
+                            .context("Failed to resolve linked file")
+                    })
+                    .inspect(|file_path| {
+                        log.info(format!("Link require points to '{}'", file_path.display()))
+                    }),
+            },
+            RequirePath::String(require) => {
+                file_path_from_string_require(path, require).inspect(|file_path| {
+                    log.info(format!("Link require points to '{}'", file_path.display()))
+                })
+            }
+        }
+    }
+
+    /// Follows a linked file which is itself a link (e.g. another wally thunk, or a package entry point doing
+    /// `return require(script.Main)`) to the module it points to, repeating until a module which is not a link is found
+    fn follow_links(
+        &self,
+        path: &Path,
+        mut file_path: PathBuf,
+        log: &mut ThunkLog,
+    ) -> Result<LinkedModule> {
+        let canonicalize = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let mut chain = vec![canonicalize(path)];
+        let mut hops = Vec::new();
+
+        loop {
+            let canonical_file_path = canonicalize(&file_path);
+            if chain.contains(&canonical_file_path) {
+                bail!(
+                    "Link chain is cyclic: {}",
+                    chain
+                        .iter()
+                        .chain(std::iter::once(&canonical_file_path))
+                        .map(|path| format!("'{}'", path.display()))
+                        .collect::<Vec<_>>()
+                        .join(" -> ")
+                );
+            }
+            chain.push(canonical_file_path);
+
+            let contents =
+                std::fs::read_to_string(&file_path).context("Failed to read linked file")?;
+
+            // Anything that does not parse as a link is the module itself
+            let Some(require_path) = full_moon::parse(&contents)
+                .ok()
+                .and_then(|ast| link_require(ast.nodes()))
+                .and_then(|returns| match_require(returns.iter().next()?).ok())
+            else {
+                return Ok(LinkedModule {
+                    path: file_path,
+                    contents,
+                    hops,
+                });
+            };
+
+            if chain.len() > MAX_LINK_CHAIN_DEPTH {
+                bail!(
+                    "Link chain starting at '{}' is longer than {} links",
+                    path.display(),
+                    MAX_LINK_CHAIN_DEPTH
+                );
+            }
+
+            log.info(format!(
+                "Linked file '{}' is itself a link to '{}', following it",
+                file_path.display(),
+                require_path
+            ));
+
+            file_path = self
+                .resolve_require(&file_path, &require_path, log)
+                .with_context(|| {
+                    format!(
+                        "Could not convert require expression in linked file '{}' to file path",
+                        file_path.display()
+                    )
+                })?;
+            hops.push(require_path);
+        }
+    }
+
+    /// Brings a file generated for a link file up to date, writing it unless checking or dry running.
+    /// A file with no new contents is removed
+    fn update_file(
+        &self,
+        path: &Path,
+        old_contents: Option<String>,
+        new_contents: Option<String>,
+        log: &mut ThunkLog,
+        result: &mut ThunkResult,
+    ) -> Result<ThunkOutcome> {
+        let outcome = if new_contents.is_some() {
+            ThunkOutcome::Successful
+        } else {
+            ThunkOutcome::Unchanged
+        };
+
+        if new_contents == old_contents {
+            if new_contents.is_some() {
+                log.info(format!(
+                    "Exported types found, '{}' is already up to date",
+                    path.display()
+                ));
+            }
+            return Ok(outcome);
+        }
+
+        result.change = Some(LinkChange {
+            path: path.to_path_buf(),
+            old_contents: old_contents.unwrap_or_default(),
+            new_contents: new_contents.clone().unwrap_or_default(),
+        });
+
+        if self.options.check {
+            log.warn(format!("'{}' is out of date", path.display()));
+            return Ok(ThunkOutcome::OutOfDate);
+        }
+
+        match new_contents {
+            _ if self.options.dry_run => {
+                log.info(format!("'{}' would be changed", path.display()));
+            }
+            Some(new_contents) => {
+                log.info(format!(
+                    "Exported types found, writing '{}'",
+                    path.display()
+                ));
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                write_atomically(path, &new_contents)?;
+            }
+            None => {
+                log.info(format!("No exported types, removing '{}'", path.display()));
+                std::fs::remove_file(path)?;
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    /// Writes the type declaration file of a link file into the types directory, leaving the link file unchanged
+    fn update_types_file(
+        &self,
+        types_dir: &Path,
+        path: &Path,
+        linked_module: &LinkedModule,
+        log: &mut ThunkLog,
+        result: &mut ThunkResult,
+    ) -> Result<ThunkOutcome> {
+        let types_path = types_file_path(types_dir, path);
+        let old_contents = std::fs::read_to_string(&types_path).ok();
+
+        let new_contents =
+            match types_file_contents(types_dir, &linked_module.path, &linked_module.contents)
+                .context("Failed to create type declaration file contents")?
+            {
+                MutateLinkResult::Changed(new_ast) => {
+                    result.exported_types = exported_type_names(&new_ast);
+                    Some(new_ast.to_string())
+                }
+                MutateLinkResult::Unchanged => {
+                    log.info("No exported types, no type declaration file needed");
+                    None
+                }
+            };
+
+        self.update_file(&types_path, old_contents, new_contents, log, result)
+    }
+
+    fn mutate_thunk(
+        &self,
+        path: &Path,
+        log: &mut ThunkLog,
+        result: &mut ThunkResult,
+    ) -> Result<ThunkOutcome> {
+        log.info(format!("Found link file '{}'", path.display()));
+
+        if let Some(types_module) = &self.options.types_module {
+            if types_module.canonicalize().ok() == path.canonicalize().ok() {
+                log.info("Link file is the types module, skipping");
+                return Ok(ThunkOutcome::Unchanged);
+            }
+        }
+
+        // Type declaration files are only needed for the packages the project depends on directly
+        if self.options.types_dir.is_some() && is_index_thunk(path) {
+            log.info("Link file is inside an _Index, skipping");
+            return Ok(ThunkOutcome::Unchanged);
+        }
+
+        // The entry should be a thunk
+        let link_contents = std::fs::read_to_string(path)?;
+        let parsed_code = match full_moon::parse(&link_contents) {
+            Ok(parsed_code) => parsed_code,
+            Err(errors) => bail!(errors
+                .iter()
+                .map(|err| err.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")),
+        };
+
+        // Links previously generated by this tool store the require in a `REQUIRED_MODULE` local
+        let returns = match generated_link_require(parsed_code.nodes()) {
+            Some(expressions) => Some(expressions),
+            None => match parsed_code.nodes().last_stmt() {
+                Some(LastStmt::Return(r#return)) => Some(r#return.returns().clone()),
+                _ => None,
+            },
+        };
+
+        if let Some(returns) = returns {
+            let Some(returned_expression) = returns.iter().next() else {
+                log.warn("Malformed link file, return statement is empty, skipping. Run `wally install` to regenerate link files");
+                return Ok(ThunkOutcome::FailedToParseReturnStmt);
+            };
+
+            let require_path = match match_require(returned_expression) {
+                Ok(require_path) => require_path,
+                Err(err) => {
+                    log.warn("Malformed link file, could not parse return expression, skipping. Run `wally install` to regenerate link files");
+                    log.error(format!("{:#}", err));
+                    return Ok(ThunkOutcome::FailedToParseReturnStmt);
+                }
+            };
+
+            log.info(format!(
+                "Require expression converted to path: '{}'",
+                require_path
+            ));
+            result.require_path = Some(require_path.clone());
+
+            let file_path = self
+                .resolve_require(path, &require_path, log)
+                .context("Could not convert require expression to file path")?;
+            let linked_module = self.follow_links(path, file_path, log)?;
+            result.linked_file = Some(linked_module.path.clone());
+
+            if let Some(types_dir) = &self.options.types_dir {
+                return self.update_types_file(types_dir, path, &linked_module, log, result);
+            }
+
+            // Only the module itself is known to export the types. Past any link, even one generated by this tool, the
+            // types are required from the end module instead, so that fixing the links in between changes nothing
+            let new_link_contents = if linked_module.hops.is_empty() {
+                mutate_link(parsed_code, returns, &linked_module.contents)
+            } else {
+                chained_require(returned_expression, &linked_module.hops).and_then(
+                    |types_expression| {
+                        mutate_forwarding_link(
+                            parsed_code,
+                            returns.clone(),
+                            std::iter::once(Pair::End(types_expression)).collect(),
+                            &linked_module.contents,
+                        )
+                    },
+                )
+            }
+            .context("Failed to create new link contents")?;
+
+            match new_link_contents {
+                MutateLinkResult::Changed(new_ast) => {
+                    result.exported_types = exported_type_names(&new_ast);
+                    self.update_file(
+                        path,
+                        Some(link_contents),
+                        Some(new_ast.to_string()),
+                        log,
+                        result,
+                    )
+                }
+                MutateLinkResult::Unchanged => {
+                    log.info("No exported types, leaving unchanged");
+                    Ok(ThunkOutcome::Unchanged)
+                }
+            }
+        } else {
+            log.warn("Malformed link file, no return statement found, skipping. Run `wally install` to regenerate link files");
+            Ok(ThunkOutcome::FailedToParseReturnStmt)
+        }
+    }
+
+    /// Fixes a single link file, buffering its output into `log`
+    fn fix_thunk_with_log(&self, path: &Path, log: &mut ThunkLog) -> ThunkResult {
+        let mut result = ThunkResult {
+            path: path.to_path_buf(),
+            require_path: None,
+            linked_file: None,
+            exported_types: Vec::new(),
+            change: None,
+            outcome: ThunkOutcome::Successful,
+        };
+
+        // Errors are handled here, to allow continuing with other link files
+        result.outcome = match self.mutate_thunk(path, log, &mut result) {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                log.error(format!("{:#}", err));
+                ThunkOutcome::Error(err)
+            }
+        };
+
+        result
+    }
+
+    /// Fixes a single link file
+    pub fn fix_thunk(&self, path: &Path) -> ThunkResult {
+        let mut log = ThunkLog::default();
+        let result = self.fix_thunk_with_log(path, &mut log);
+        log.flush();
+
+        result
+    }
+
+    /// Fixes all given link files, spreading them over `Options::jobs` threads. Output is logged per link file,
+    /// and results are returned in the order of `thunks`
+    pub fn fix_thunks(&self, thunks: &[PathBuf]) -> Vec<ThunkResult> {
+        let next_thunk = AtomicUsize::new(0);
+        let results = Mutex::new(thunks.iter().map(|_| None).collect::<Vec<_>>());
+        let output_lock = Mutex::new(());
+
+        let worker = || loop {
+            let index = next_thunk.fetch_add(1, Ordering::Relaxed);
+            let Some(thunk) = thunks.get(index) else {
+                break;
+            };
+
+            let mut log = ThunkLog::default();
+            let result = self.fix_thunk_with_log(thunk, &mut log);
+            results.lock().unwrap()[index] = Some(result);
+
+            let _guard = output_lock.lock().unwrap();
+            log.flush();
+        };
+
+        let jobs = self.options.jobs.clamp(1, thunks.len().max(1));
+        if jobs == 1 {
+            worker();
+        } else {
+            std::thread::scope(|scope| {
+                for _ in 0..jobs {
+                    scope.spawn(worker);
+                }
+            });
+        }
+
+        results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|result| result.expect("every link file is fixed by a worker"))
+            .collect()
+    }
+
+    /// Fixes all link files in a packages folder, including the ones inside its `_Index`
+    pub fn fix_packages_folder(&self, path: &Path) -> Result<PackagesFolderResult> {
+        let (thunks, complete) = find_thunks(path)?;
+
+        Ok(PackagesFolderResult {
+            path: path.to_path_buf(),
+            complete,
+            thunks: self.fix_thunks(&thunks),
+        })
+    }
+}
+
+fn find_index_thunks(path: &Path, thunks: &mut Vec<PathBuf>) -> Result<()> {
+    for package_entry in std::fs::read_dir(path)?.flatten() {
+        for thunk in std::fs::read_dir(package_entry.path())?.flatten() {
+            if thunk.file_type().unwrap().is_file() {
+                thunks.push(thunk.path());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds all link files in a packages folder, including the ones inside its `_Index`.
+/// The returned flag is false if part of the `_Index` could not be read
+pub fn find_thunks(path: &Path) -> Result<(Vec<PathBuf>, bool)> {
+    let mut complete = true;
+    let mut thunks = Vec::new();
+
+    for entry in std::fs::read_dir(path)
+        .context("Failed to read packages folder")?
+        .flatten()
+    {
+        if entry.file_name() == "_Index" {
+            if let Err(err) = find_index_thunks(&entry.path(), &mut thunks) {
+                error!("{:#}", err);
+                complete = false;
+            }
+            continue;
+        }
+
+        thunks.push(entry.path());
+    }
+
+    Ok((thunks, complete))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    const PROMISE_LINK: &str =
+        "return require(script.Parent._Index[\"acme_promise@1.0.0\"][\"promise\"])\n";
+
+    /// Creates a packages folder with a `Promise` link to a package exporting a type
+    fn packages_folder(root: &Path) -> PathBuf {
+        let packages = root.join("Packages");
+        std::fs::create_dir_all(packages.join("_Index/acme_promise@1.0.0/promise")).unwrap();
+        std::fs::write(
+            packages.join("_Index/acme_promise@1.0.0/promise/init.lua"),
+            "export type Status = string\nreturn {}\n",
+        )
+        .unwrap();
+        std::fs::write(packages.join("Promise.lua"), PROMISE_LINK).unwrap();
+
+        packages
+    }
+
+    #[test]
+    fn checks_links_without_writing_them() {
+        let root = TempDir::new("fixer-check");
+        let link = packages_folder(&root).join("Promise.lua");
+        let check = Fixer::without_sourcemap(Options::default().check(true));
+
+        let result = check.fix_thunk(&link);
+        assert!(matches!(result.outcome, ThunkOutcome::OutOfDate));
+        assert!(result.change.is_some());
+        assert_eq!(std::fs::read_to_string(&link).unwrap(), PROMISE_LINK);
+
+        let fix = Fixer::without_sourcemap(Options::default());
+        assert!(matches!(
+            fix.fix_thunk(&link).outcome,
+            ThunkOutcome::Successful
+        ));
+
+        let result = check.fix_thunk(&link);
+        assert!(matches!(result.outcome, ThunkOutcome::Successful));
+        assert!(result.change.is_none());
+    }
+
+    #[test]
+    fn keeps_the_order_of_link_files_when_fixing_concurrently() {
+        let root = TempDir::new("fixer-jobs");
+        let packages = packages_folder(&root);
+        let thunks: Vec<PathBuf> = (0..32)
+            .map(|index| {
+                let link = packages.join(format!("Promise{index}.lua"));
+                std::fs::write(&link, PROMISE_LINK).unwrap();
+                link
+            })
+            .collect();
+
+        let fixer = Fixer::without_sourcemap(Options::default().jobs(4).dry_run(true));
+        let results = fixer.fix_thunks(&thunks);
+        assert!(results.iter().all(ThunkResult::is_success));
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| &result.path)
+                .collect::<Vec<_>>(),
+            thunks.iter().collect::<Vec<_>>()
+        );
+    }
+
+    fn error_message(result: ThunkResult) -> String {
+        match result.outcome {
+            ThunkOutcome::Error(err) => format!("{err:#}"),
+            outcome => panic!("expected an error, got {outcome:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_cyclic_link_chains() {
+        let root = TempDir::new("fixer-cycle");
+        std::fs::write(root.join("A.lua"), "return require(script.Parent.B)\n").unwrap();
+        std::fs::write(root.join("B.lua"), "return require(script.Parent.A)\n").unwrap();
+
+        let fixer = Fixer::without_sourcemap(Options::default());
+        assert!(
+            error_message(fixer.fix_thunk(&root.join("A.lua"))).contains("Link chain is cyclic")
+        );
+    }
+
+    #[test]
+    fn limits_the_length_of_link_chains() {
+        let root = TempDir::new("fixer-depth");
+        let links = MAX_LINK_CHAIN_DEPTH + 1;
+        for index in 0..links {
+            std::fs::write(
+                root.join(format!("Link{index}.lua")),
+                format!("return require(script.Parent.Link{})\n", index + 1),
+            )
+            .unwrap();
+        }
+        std::fs::write(
+            root.join(format!("Link{links}.lua")),
+            "export type Status = string\nreturn {}\n",
+        )
+        .unwrap();
+
+        let fixer = Fixer::without_sourcemap(Options::default().dry_run(true));
+        assert!(error_message(fixer.fix_thunk(&root.join("Link0.lua")))
+            .contains(&format!("longer than {MAX_LINK_CHAIN_DEPTH} links")));
+        assert!(fixer.fix_thunk(&root.join("Link1.lua")).is_success());
+    }
+
+    const FOO_LINK: &str = "return require(script.Parent._Index[\"acme_foo@1.0.0\"][\"foo\"])\n";
+    const FIXED_FOO_LINK: &str = "local REQUIRED_MODULE = require(script.Parent._Index[\"acme_foo@1.0.0\"][\"foo\"])\nlocal REQUIRED_TYPES = require(script.Parent._Index[\"acme_foo@1.0.0\"][\"foo\"].Parent.Parent[\"acme_promise@1.0.0\"].promise)\nexport type Status = REQUIRED_TYPES.Status \nreturn REQUIRED_MODULE\n";
+
+    /// Adds a `Foo` package whose entry point forwards to its `Promise` dependency link, returning that link
+    fn forwarding_package(packages: &Path) -> PathBuf {
+        let foo = packages.join("_Index/acme_foo@1.0.0");
+        std::fs::create_dir_all(foo.join("foo")).unwrap();
+        std::fs::write(
+            foo.join("foo/init.lua"),
+            "return require(script.Parent.Promise)\n",
+        )
+        .unwrap();
+        std::fs::write(
+            foo.join("Promise.lua"),
+            "return require(script.Parent.Parent[\"acme_promise@1.0.0\"][\"promise\"])\n",
+        )
+        .unwrap();
+
+        foo.join("Promise.lua")
+    }
+
+    #[test]
+    fn requires_types_from_the_end_of_link_chains() {
+        let root = TempDir::new("fixer-chain");
+        let packages = packages_folder(&root);
+        let index_link = forwarding_package(&packages);
+        let link = packages.join("Foo.lua");
+        std::fs::write(&link, FOO_LINK).unwrap();
+
+        // The package's entry point forwards to a plain link, which exports no types
+        let fixer = Fixer::without_sourcemap(Options::default());
+        assert!(matches!(
+            fixer.fix_thunk(&link).outcome,
+            ThunkOutcome::Successful
+        ));
+        assert_eq!(std::fs::read_to_string(&link).unwrap(), FIXED_FOO_LINK);
+
+        // Fixing the link in between does not change the chain
+        assert!(matches!(
+            fixer.fix_thunk(&index_link).outcome,
+            ThunkOutcome::Successful
+        ));
+        let result = fixer.fix_thunk(&link);
+        assert!(matches!(result.outcome, ThunkOutcome::Successful));
+        assert!(result.change.is_none());
+    }
+
+    #[test]
+    fn follows_links_written_by_other_workers() {
+        let root = TempDir::new("fixer-chain-jobs");
+        let packages = packages_folder(&root);
+        let mut thunks = vec![forwarding_package(&packages)];
+        thunks.extend((0..32).map(|index| {
+            let link = packages.join(format!("Foo{index}.lua"));
+            std::fs::write(&link, FOO_LINK).unwrap();
+            link
+        }));
+
+        let fixer = Fixer::without_sourcemap(Options::default().jobs(4));
+        assert!(fixer
+            .fix_thunks(&thunks)
+            .iter()
+            .all(ThunkResult::is_success));
+        for link in &thunks[1..] {
+            assert_eq!(std::fs::read_to_string(link).unwrap(), FIXED_FOO_LINK);
+        }
+        assert!(std::fs::read_dir(packages.join("_Index/acme_foo@1.0.0"))
+            .unwrap()
+            .all(|entry| !entry
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .ends_with(".tmp")));
+    }
+
+    #[test]
+    fn only_fails_link_files_needing_removed_sourcemap_files() {
+        let root = TempDir::new("fixer-stale-sourcemap");
+        let packages = packages_folder(&root);
+        std::fs::write(
+            packages.join("Signal.lua"),
+            "return require(script.Parent._Index[\"acme_signal@1.0.0\"][\"signal\"])\n",
+        )
+        .unwrap();
+        let mut sourcemap: SourcemapNode = serde_json::from_str(
+            r#"{
+                "name": "Packages",
+                "className": "Folder",
+                "filePaths": ["Packages"],
+                "children": [
+                    { "name": "Promise", "className": "ModuleScript", "filePaths": ["Packages/Promise.lua"] },
+                    { "name": "Signal", "className": "ModuleScript", "filePaths": ["Packages/Signal.lua"] },
+                    {
+                        "name": "_Index",
+                        "className": "Folder",
+                        "children": [
+                            {
+                                "name": "acme_promise@1.0.0",
+                                "className": "Folder",
+                                "children": [
+                                    { "name": "promise", "className": "ModuleScript", "filePaths": ["Packages/_Index/acme_promise@1.0.0/promise/init.lua"] }
+                                ]
+                            },
+                            {
+                                "name": "acme_signal@1.0.0",
+                                "className": "Folder",
+                                "children": [
+                                    { "name": "signal", "className": "ModuleScript", "filePaths": ["Packages/_Index/acme_signal@1.0.0/signal/init.lua"] }
+                                ]
+                            }
+                        ]
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        mutate_sourcemap(&mut sourcemap, &root).unwrap();
+        let fixer = Fixer::new(&sourcemap, Options::default().dry_run(true));
+
+        assert!(fixer.fix_thunk(&packages.join("Promise.lua")).is_success());
+
+        let mut log = ThunkLog::default();
+        let result = fixer.fix_thunk_with_log(&packages.join("Signal.lua"), &mut log);
+        assert!(error_message(result).contains("regenerate the sourcemap"));
+        assert!(log
+            .messages(log::Level::Warn)
+            .any(|message| message.contains("the sourcemap may be out of date")));
+    }
+
+    #[test]
+    fn writes_and_removes_type_declaration_files() {
+        let root = TempDir::new("fixer-types-dir");
+        let packages = packages_folder(&root);
+        let index_link = packages.join("_Index/acme_promise@1.0.0/Promise.lua");
+        std::fs::write(&index_link, "return require(script.Parent.promise)\n").unwrap();
+        let types_dir = root.join("PackageTypes");
+        let fixer = Fixer::without_sourcemap(Options::default().types_dir(Some(types_dir.clone())));
+
+        let result = fixer.fix_thunk(&packages.join("Promise.lua"));
+        assert!(matches!(result.outcome, ThunkOutcome::Successful));
+        assert_eq!(result.exported_types, ["Status"]);
+        assert_eq!(
+            std::fs::read_to_string(types_dir.join("Promise.luau")).unwrap(),
+            "local REQUIRED_MODULE = require(\"../Packages/_Index/acme_promise@1.0.0/promise\")\nexport type Status = REQUIRED_MODULE.Status \nreturn REQUIRED_MODULE\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(packages.join("Promise.lua")).unwrap(),
+            PROMISE_LINK
+        );
+        assert!(matches!(
+            fixer.fix_thunk(&index_link).outcome,
+            ThunkOutcome::Unchanged
+        ));
+
+        std::fs::write(
+            packages.join("_Index/acme_promise@1.0.0/promise/init.lua"),
+            "return {}\n",
+        )
+        .unwrap();
+        let result = fixer.fix_thunk(&packages.join("Promise.lua"));
+        assert!(matches!(result.outcome, ThunkOutcome::Unchanged));
+        assert!(!types_dir.join("Promise.luau").exists());
+    }
+}
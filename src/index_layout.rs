@@ -0,0 +1,225 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+use crate::project::project_root_path;
+use crate::require_parser::*;
+use crate::sourcemap::normalize_path;
+
+const MODULE_EXTENSIONS: &[&str] = &["luau", "lua"];
+
+/// Finds the module file of an instance, i.e. the file itself, or the `init` file of a directory
+fn module_file(instance: &Path) -> Option<PathBuf> {
+    if instance.is_file() {
+        return Some(instance.to_path_buf());
+    }
+
+    MODULE_EXTENSIONS
+        .iter()
+        .map(|extension| instance.join("init").with_extension(extension))
+        .find(|path| path.is_file())
+}
+
+/// Finds the child of a directory instance with the given name, either a directory or a `.luau`/`.lua` file
+fn find_child(instance: &Path, name: &str) -> Option<PathBuf> {
+    if !instance.is_dir() {
+        return None;
+    }
+
+    let directory = instance.join(name);
+    if directory.is_dir() {
+        return Some(directory);
+    }
+
+    MODULE_EXTENSIONS
+        .iter()
+        .map(|extension| instance.join(format!("{name}.{extension}")))
+        .find(|path| path.is_file())
+}
+
+/// Follows the `default.project.json` of a package directory to the file or directory its tree points to, since most
+/// wally packages keep their code in e.g. `lib` or `src`
+fn project_root(mut path: PathBuf) -> Result<PathBuf> {
+    let mut visited = Vec::new();
+
+    loop {
+        let project_path = path.join("default.project.json");
+        if !project_path.is_file() || visited.contains(&path) {
+            return Ok(path);
+        }
+
+        let Some(root) = project_root_path(&project_path)? else {
+            return Ok(path);
+        };
+        visited.push(path);
+        path = normalize_path(&root);
+    }
+}
+
+/// An instance of the wally layout, with the file or directory it consists of
+struct Instance {
+    name: String,
+    path: PathBuf,
+}
+
+/// Given a list of components (e.g., ['script', 'Parent', '_Index', 'scope_name@1.0.0', 'name']), converts it to a
+/// file path without a sourcemap, by mapping instances straight onto the files and directories of the wally layout.
+/// A directory is an instance whose module is its `init.luau`/`init.lua` file, or the root of its `default.project.json`
+pub fn file_path_from_index_layout(
+    path: &Path,
+    path_components: &[PathComponent],
+) -> Result<PathBuf> {
+    let mut iter = path_components.iter();
+    let first_in_chain = iter.next().context("No path components")?;
+
+    if *first_in_chain != "script" {
+        bail!("require expression does not start with 'script', which is needed to resolve it without a sourcemap");
+    }
+
+    // An `init` file is its directory's module
+    let script = match path.file_stem() {
+        Some(stem) if stem == "init" => {
+            path.parent().context("Link file has no parent directory")?
+        }
+        _ => path,
+    };
+
+    // The instances from the root down to the current one. Directories reached through a project do not mirror the
+    // instance tree, so parents are taken from here rather than from the file system
+    let mut instances: Vec<Instance> = script
+        .ancestors()
+        .map(|ancestor| Instance {
+            name: if ancestor.is_file() {
+                ancestor.file_stem()
+            } else {
+                ancestor.file_name()
+            }
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+            path: ancestor.to_path_buf(),
+        })
+        .collect();
+    instances.reverse();
+
+    for component in iter {
+        match component {
+            PathComponent::Child(name) if name == "Parent" => {
+                instances.pop();
+                if instances.is_empty() {
+                    bail!("No parent found in linked components");
+                }
+            }
+            PathComponent::Child(name) => {
+                let current = &instances.last().unwrap().path;
+                let child = find_child(current, name).with_context(|| {
+                    format!("Child '{name}' not found in '{}'", current.display())
+                })?;
+                instances.push(Instance {
+                    name: name.clone(),
+                    path: project_root(child)?,
+                });
+            }
+            PathComponent::Ancestor(name) => {
+                instances.pop();
+                while instances
+                    .last()
+                    .is_some_and(|instance| instance.name != *name)
+                {
+                    instances.pop();
+                }
+                if instances.is_empty() {
+                    bail!("Ancestor '{name}' not found in linked components");
+                }
+            }
+        }
+    }
+
+    let current = &instances.last().unwrap().path;
+    module_file(current)
+        .with_context(|| format!("No .lua/.luau file found for '{}'", current.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn resolves_requires_from_the_index_layout() {
+        let root = TempDir::new("index-layout");
+        std::fs::create_dir_all(root.join("Packages/_Index/acme_promise@1.0.0/promise")).unwrap();
+        std::fs::write(root.join("Packages/Promise.lua"), "").unwrap();
+        std::fs::write(
+            root.join("Packages/_Index/acme_promise@1.0.0/promise/init.luau"),
+            "",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("Packages/_Index/acme_promise@1.0.0/Signal.lua"),
+            "",
+        )
+        .unwrap();
+
+        let components = |names: &[&str]| -> Vec<PathComponent> {
+            names
+                .iter()
+                .map(|name| PathComponent::Child(name.to_string()))
+                .collect()
+        };
+
+        assert_eq!(
+            file_path_from_index_layout(
+                &root.join("Packages/Promise.lua"),
+                &components(&[
+                    "script",
+                    "Parent",
+                    "_Index",
+                    "acme_promise@1.0.0",
+                    "promise"
+                ])
+            )
+            .unwrap(),
+            root.join("Packages/_Index/acme_promise@1.0.0/promise/init.luau")
+        );
+        assert_eq!(
+            file_path_from_index_layout(
+                &root.join("Packages/_Index/acme_promise@1.0.0/promise/init.luau"),
+                &components(&["script", "Parent", "Signal"])
+            )
+            .unwrap(),
+            root.join("Packages/_Index/acme_promise@1.0.0/Signal.lua")
+        );
+
+        // Packages with a project keep their code in the directory its tree points to
+        let package = root.join("Packages/_Index/acme_signal@1.0.0/signal");
+        std::fs::create_dir_all(package.join("lib")).unwrap();
+        std::fs::write(
+            package.join("default.project.json"),
+            r#"{"name": "signal", "tree": {"$path": "lib"}}"#,
+        )
+        .unwrap();
+        std::fs::write(package.join("lib/init.lua"), "").unwrap();
+        std::fs::write(package.join("lib/Util.lua"), "").unwrap();
+        let signal = ["script", "Parent", "_Index", "acme_signal@1.0.0", "signal"];
+        assert_eq!(
+            file_path_from_index_layout(&root.join("Packages/Signal.lua"), &components(&signal))
+                .unwrap(),
+            package.join("lib/init.lua")
+        );
+        assert_eq!(
+            file_path_from_index_layout(
+                &root.join("Packages/Signal.lua"),
+                &components(&[&signal[..], &["Parent", "signal", "Util"]].concat())
+            )
+            .unwrap(),
+            package.join("lib/Util.lua")
+        );
+
+        assert!(file_path_from_index_layout(
+            &root.join("Packages/Promise.lua"),
+            &components(&["game", "ReplicatedStorage"])
+        )
+        .is_err());
+    }
+}
@@ -1,6 +1,48 @@
+//! Fixes [wally](https://github.com/UpliftGames/wally) package link files (thunks) so that they
+//! re-export the Luau types of the modules they point to.
+//!
+//! The [`Command`] is the command line interface. To embed the fixer in other tools, load a sourcemap,
+//! normalize it with [`mutate_sourcemap`] against the directory its paths are relative to, and pass it to a [`Fixer`]:
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use wally_package_types::{mutate_sourcemap, Fixer, Options, SourcemapNode};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let mut sourcemap: SourcemapNode =
+//!     serde_json::from_str(&std::fs::read_to_string("sourcemap.json")?)?;
+//! mutate_sourcemap(&mut sourcemap, Path::new("."))?;
+//!
+//! let fixer = Fixer::new(&sourcemap, Options::default().jobs(4));
+//! let result = fixer.fix_packages_folder(Path::new("Packages"))?;
+//! for thunk in &result.thunks {
+//!     println!("{}: {:?}", thunk.path.display(), thunk.outcome);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
 mod command;
+mod diff;
+mod fixer;
+mod index_layout;
 mod link_mutator;
+mod package_types;
+mod project;
+mod report;
 mod require_parser;
+mod revert;
 mod sourcemap;
+mod string_require;
+#[cfg(test)]
+mod test_util;
+mod thunk_log;
+mod watch;
 
-pub use command::Command;
+pub use command::{Command, Subcommand};
+pub use fixer::{Fixer, LinkChange, Options, PackagesFolderResult, ThunkOutcome, ThunkResult};
+pub use link_mutator::{mutate_link, type_declarations_from_source, MutateLinkResult};
+pub use project::sourcemap_from_project;
+pub use require_parser::{match_require, PathComponent, RequirePath};
+pub use revert::{revert_packages_folder, revert_thunk};
+pub use sourcemap::{mutate_sourcemap, SourcemapNode};
@@ -3,11 +3,11 @@ use full_moon::{
     ast::{
         luau::{
             ExportedTypeDeclaration, GenericDeclaration, GenericDeclarationParameter,
-            GenericParameterInfo, IndexedTypeInfo, TypeInfo,
+            GenericParameterInfo, IndexedTypeInfo, TypeFieldKey, TypeInfo,
         },
         punctuated::{Pair, Punctuated},
         span::ContainedSpan,
-        Ast, Expression, LastStmt, LocalAssignment, Return, Stmt,
+        Ast, Block, Expression, LastStmt, LocalAssignment, Return, Stmt,
     },
     tokenizer::{Token, TokenReference, TokenType},
 };
@@ -33,32 +33,260 @@ pub fn type_declarations_from_source(code: &str) -> Result<Vec<ExportedTypeDecla
         .collect())
 }
 
+/// Types which are always available in Luau
+const BUILTIN_TYPES: &[&str] = &[
+    "any", "boolean", "buffer", "never", "nil", "number", "string", "thread", "unknown",
+    "userdata", "vector",
+];
+
+/// Lists the names of the parameters of a generic declaration, e.g. `["T", "U"]` for `<T, U...>`
+fn generic_names(generics: &GenericDeclaration) -> Vec<String> {
+    generics
+        .generics()
+        .iter()
+        .map(|generic| match generic.parameter() {
+            GenericParameterInfo::Name(name) => name.token().to_string(),
+            GenericParameterInfo::Variadic { name, .. } => name.token().to_string(),
+            other => unreachable!("unknown node: {:?}", other),
+        })
+        .collect()
+}
+
+/// Whether a default type can be kept in a re-exported type declaration, i.e. whether it only
+/// references builtin types, literals, and the given resolved types
 fn should_keep_default_type(type_info: &TypeInfo, resolved_types: &[String]) -> bool {
-    // TODO: we could be more clever here, but for now we keep it simple
+    let keep = |type_info: &TypeInfo| should_keep_default_type(type_info, resolved_types);
+    let is_resolved = |name: &TokenReference| {
+        let name = name.token().to_string();
+        BUILTIN_TYPES.contains(&name.as_str()) || resolved_types.contains(&name)
+    };
+
     match type_info {
-        TypeInfo::Basic(name) => resolved_types.contains(&name.token().to_string()),
-        TypeInfo::Boolean(_) => true,
+        TypeInfo::Basic(name) => is_resolved(name),
+        TypeInfo::String(_) | TypeInfo::Boolean(_) => true,
+        TypeInfo::Array { type_info, .. }
+        | TypeInfo::Optional {
+            base: type_info, ..
+        }
+        | TypeInfo::Variadic { type_info, .. } => keep(type_info),
+        TypeInfo::Generic { base, generics, .. } => is_resolved(base) && generics.iter().all(keep),
+        TypeInfo::GenericPack { name, .. } | TypeInfo::VariadicPack { name, .. } => {
+            resolved_types.contains(&name.token().to_string())
+        }
+        TypeInfo::Tuple { types, .. } => types.iter().all(keep),
+        TypeInfo::Union(union) => union.types().iter().all(keep),
+        TypeInfo::Intersection(intersection) => intersection.types().iter().all(keep),
+        TypeInfo::Table { fields, .. } => fields.iter().all(|field| {
+            let key_is_resolved = match field.key() {
+                TypeFieldKey::Name(_) => true,
+                TypeFieldKey::IndexSignature { inner, .. } => keep(inner),
+                _ => false,
+            };
+            key_is_resolved && keep(field.value())
+        }),
+        TypeInfo::Callback {
+            generics,
+            arguments,
+            return_type,
+            ..
+        } => {
+            // The callback's own generics are only resolved within it
+            let mut resolved_types = resolved_types.to_vec();
+            resolved_types.extend(generics.iter().flat_map(generic_names));
+            arguments
+                .iter()
+                .all(|argument| should_keep_default_type(argument.type_info(), &resolved_types))
+                && should_keep_default_type(return_type, &resolved_types)
+        }
+        // Module types and `typeof` reference locals of the original module, which are not available
         _ => false,
     }
 }
 
+/// Creates the type `<module_name>.<type_info>`, referencing a type exported by the module stored in the local `module_name`
+fn module_type(module_name: &str, type_info: IndexedTypeInfo) -> TypeInfo {
+    TypeInfo::Module {
+        module: TokenReference::new(
+            vec![],
+            Token::new(TokenType::Identifier {
+                identifier: module_name.into(),
+            }),
+            vec![],
+        ),
+        punctuation: TokenReference::symbol(".").unwrap(),
+        type_info: Box::new(type_info),
+    }
+}
+
+/// Qualifies references to the given exported types with the module stored in the local `module_name`,
+/// e.g. `State` becomes `REQUIRED_MODULE.State`, since they are not in scope in the link
+fn qualify_exported_types(type_info: &mut TypeInfo, exported_types: &[String], module_name: &str) {
+    let qualify =
+        |type_info: &mut TypeInfo| qualify_exported_types(type_info, exported_types, module_name);
+    let is_exported = |name: &TokenReference| exported_types.contains(&name.token().to_string());
+
+    match type_info {
+        TypeInfo::Basic(name) if is_exported(name) => {
+            let name = name.clone();
+            *type_info = module_type(module_name, IndexedTypeInfo::Basic(name));
+        }
+        TypeInfo::Generic {
+            base,
+            arrows,
+            generics,
+        } => {
+            for generic in generics.iter_mut() {
+                qualify(generic);
+            }
+            if is_exported(base) {
+                let indexed_type_info = IndexedTypeInfo::Generic {
+                    base: base.clone(),
+                    arrows: arrows.clone(),
+                    generics: generics.clone(),
+                };
+                *type_info = module_type(module_name, indexed_type_info);
+            }
+        }
+        TypeInfo::Array { type_info, .. }
+        | TypeInfo::Optional {
+            base: type_info, ..
+        }
+        | TypeInfo::Variadic { type_info, .. } => qualify(type_info),
+        TypeInfo::Tuple { types, .. } => {
+            for type_info in types.iter_mut() {
+                qualify(type_info);
+            }
+        }
+        TypeInfo::Union(union) => {
+            let mut types = union.types().clone();
+            for type_info in types.iter_mut() {
+                qualify(type_info);
+            }
+            *union = union.clone().with_types(types);
+        }
+        TypeInfo::Intersection(intersection) => {
+            let mut types = intersection.types().clone();
+            for type_info in types.iter_mut() {
+                qualify(type_info);
+            }
+            *intersection = intersection.clone().with_types(types);
+        }
+        TypeInfo::Table { fields, .. } => {
+            for field in fields.iter_mut() {
+                let key = match field.key() {
+                    TypeFieldKey::IndexSignature { brackets, inner } => {
+                        let mut inner = inner.clone();
+                        qualify(&mut inner);
+                        TypeFieldKey::IndexSignature {
+                            brackets: brackets.clone(),
+                            inner,
+                        }
+                    }
+                    key => key.clone(),
+                };
+                let mut value = field.value().clone();
+                qualify(&mut value);
+                *field = field.clone().with_key(key).with_value(value);
+            }
+        }
+        TypeInfo::Callback {
+            generics,
+            arguments,
+            return_type,
+            ..
+        } => {
+            // The callback's own generics shadow exported types within it
+            let own_generics: Vec<String> = generics.iter().flat_map(generic_names).collect();
+            let exported_types: Vec<String> = exported_types
+                .iter()
+                .filter(|name| !own_generics.contains(name))
+                .cloned()
+                .collect();
+
+            for argument in arguments.iter_mut() {
+                let mut type_info = argument.type_info().clone();
+                qualify_exported_types(&mut type_info, &exported_types, module_name);
+                *argument = argument.clone().with_type_info(type_info);
+            }
+            qualify_exported_types(return_type, &exported_types, module_name);
+        }
+        _ => {}
+    }
+}
+
+/// Removes the default types which are not resolvable in the link, qualifying references to `exported_types`
+/// in the ones which are kept
 fn strip_unknown_default_generics(
     generics: &GenericDeclaration,
     resolved_types: &[String],
+    exported_types: &[String],
+    module_name: &str,
 ) -> Punctuated<GenericDeclarationParameter> {
     generics
         .generics()
         .pairs()
         .map(|pair| {
-            pair.clone().map(|decl| match decl.default_type() {
-                Some(type_info) if should_keep_default_type(type_info, resolved_types) => decl,
-                _ => decl.with_default(None),
-            })
+            pair.clone()
+                .map(|decl| match (decl.equals(), decl.default_type()) {
+                    (Some(equals), Some(type_info))
+                        if should_keep_default_type(type_info, resolved_types) =>
+                    {
+                        let mut type_info = type_info.clone();
+                        qualify_exported_types(&mut type_info, exported_types, module_name);
+                        let equals = equals.clone();
+                        decl.with_default(Some((equals, type_info)))
+                    }
+                    _ => decl.with_default(None),
+                })
         })
         .collect::<Punctuated<_>>()
 }
 
-pub fn create_new_type_declaration(stmt: &ExportedTypeDeclaration) -> ExportedTypeDeclaration {
+/// Extracts the block of comments directly above a declaration, i.e. its documentation, with each comment on its own line.
+/// Comments separated from the declaration by a blank line, and directives such as `--!strict`, are not part of it
+fn doc_comment_trivia(token: &TokenReference) -> Vec<Token> {
+    let mut comments = Vec::new();
+    let mut newlines = 0;
+
+    for trivia in token.leading_trivia().collect::<Vec<_>>().into_iter().rev() {
+        match trivia.token_type() {
+            TokenType::Whitespace { characters } => {
+                newlines += characters.matches('\n').count();
+                if newlines >= 2 {
+                    break;
+                }
+                continue;
+            }
+            TokenType::SingleLineComment { comment } if !comment.starts_with('!') => {
+                comments.push(trivia.clone())
+            }
+            TokenType::MultiLineComment { .. } => comments.push(trivia.clone()),
+            _ => break,
+        }
+        newlines = 0;
+    }
+
+    comments
+        .into_iter()
+        .rev()
+        .flat_map(|comment| {
+            [
+                comment,
+                Token::new(TokenType::Whitespace {
+                    characters: "\n".into(),
+                }),
+            ]
+        })
+        .collect()
+}
+
+/// Creates a type declaration re-exporting `stmt` from the module stored in the local `module_name`.
+/// `exported_types` are the names of all types exported by the module
+pub fn create_new_type_declaration(
+    stmt: &ExportedTypeDeclaration,
+    module_name: &str,
+    exported_types: &[String],
+) -> ExportedTypeDeclaration {
     let type_info = match stmt.type_declaration().generics() {
         Some(generics) => IndexedTypeInfo::Generic {
             base: stmt.type_declaration().type_name().clone(),
@@ -87,55 +315,66 @@ pub fn create_new_type_declaration(stmt: &ExportedTypeDeclaration) -> ExportedTy
     };
 
     // Modify the original type declaration to remove the default generics, if they are not resolvable
-    let resolved_types = stmt
+    let generics = stmt
         .type_declaration()
         .generics()
-        .map_or(vec![], |generics| {
-            generics
-                .generics()
-                .iter()
-                .map(|generic| match generic.parameter() {
-                    GenericParameterInfo::Name(name) => name.token().to_string(),
-                    GenericParameterInfo::Variadic { name, .. } => name.token().to_string(),
-                    other => unreachable!("unknown node: {:?}", other),
-                })
-                .collect()
-        });
+        .map_or(vec![], generic_names);
+    let resolved_types: Vec<String> = generics.iter().chain(exported_types).cloned().collect();
+    // Generic parameters shadow exported types of the same name
+    let qualified_types: Vec<String> = exported_types
+        .iter()
+        .filter(|name| !generics.contains(name))
+        .cloned()
+        .collect();
 
     let original_type_declaration = match stmt.type_declaration().generics() {
-        Some(generics) => stmt.type_declaration().clone().with_generics(Some(
-            generics
+        Some(generics) => {
+            stmt.type_declaration()
                 .clone()
-                .with_generics(strip_unknown_default_generics(generics, &resolved_types)),
-        )),
+                .with_generics(Some(generics.clone().with_generics(
+                    strip_unknown_default_generics(
+                        generics,
+                        &resolved_types,
+                        &qualified_types,
+                        module_name,
+                    ),
+                )))
+        }
         None => stmt.type_declaration().clone(),
     };
 
     // Can't use TypeDeclaration::new(), since it always panics
-    let type_declaration = original_type_declaration.with_type_definition(TypeInfo::Module {
-        module: TokenReference::new(
-            vec![],
-            Token::new(TokenType::Identifier {
-                identifier: "REQUIRED_MODULE".into(),
-            }),
-            vec![],
-        ),
-        punctuation: TokenReference::symbol(".").unwrap(),
-        type_info: Box::new(type_info),
-    });
+    let type_declaration =
+        original_type_declaration.with_type_definition(module_type(module_name, type_info));
 
-    ExportedTypeDeclaration::new(type_declaration)
+    ExportedTypeDeclaration::new(type_declaration).with_export_token(TokenReference::new(
+        doc_comment_trivia(stmt.export_token()),
+        Token::new(TokenType::Identifier {
+            identifier: "export".into(),
+        }),
+        vec![Token::new(TokenType::spaces(1))],
+    ))
 }
 
 // Creates a list of re-exported type declarations from the type declarations found in the source file
 fn re_export_type_declarations(
     stmts: Vec<ExportedTypeDeclaration>,
+    module_name: &str,
 ) -> Vec<(Stmt, Option<TokenReference>)> {
+    let exported_types: Vec<String> = stmts
+        .iter()
+        .map(|stmt| stmt.type_declaration().type_name().token().to_string())
+        .collect();
+
     stmts
         .iter()
         .map(|stmt| {
             (
-                Stmt::ExportedTypeDeclaration(create_new_type_declaration(stmt)),
+                Stmt::ExportedTypeDeclaration(create_new_type_declaration(
+                    stmt,
+                    module_name,
+                    &exported_types,
+                )),
                 Some(TokenReference::new(
                     vec![],
                     Token::new(TokenType::Whitespace {
@@ -148,9 +387,21 @@ fn re_export_type_declarations(
         .collect()
 }
 
+/// Creates a symbol token, such as `local `, with the given leading trivia
+fn symbol_with_leading_trivia(symbol: &str, leading_trivia: Vec<Token>) -> TokenReference {
+    let symbol = TokenReference::symbol(symbol).unwrap();
+    TokenReference::new(
+        leading_trivia,
+        symbol.token().clone(),
+        symbol.trailing_trivia().cloned().collect(),
+    )
+}
+
 /// Extracts a require expression out into a local variable of form `local REQUIRED_MODULE = ...`
 fn extract_require_into_local_stmt(
+    name: &str,
     return_expressions: Punctuated<Expression>,
+    leading_trivia: Vec<Token>,
 ) -> (Stmt, Option<TokenReference>) {
     (
         Stmt::LocalAssignment(
@@ -158,12 +409,13 @@ fn extract_require_into_local_stmt(
                 std::iter::once(Pair::End(TokenReference::new(
                     vec![],
                     Token::new(TokenType::Identifier {
-                        identifier: "REQUIRED_MODULE".into(),
+                        identifier: name.into(),
                     }),
                     vec![],
                 )))
                 .collect(),
             )
+            .with_local_token(symbol_with_leading_trivia("local ", leading_trivia))
             .with_equal_token(Some(TokenReference::symbol(" = ").unwrap()))
             .with_expressions(return_expressions),
         ),
@@ -192,8 +444,147 @@ fn create_return_require_variable() -> (LastStmt, Option<TokenReference>) {
     )
 }
 
+/// Lists the names of all exported types in a source file
+pub fn exported_type_names(ast: &Ast) -> Vec<String> {
+    ast.nodes()
+        .stmts()
+        .filter_map(|stmt| match stmt {
+            Stmt::ExportedTypeDeclaration(stmt) => {
+                Some(stmt.type_declaration().type_name().token().to_string())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Recovers the require expressions from a link previously generated by this tool, i.e. one of the form
+/// `local REQUIRED_MODULE = require(...)` followed by `return REQUIRED_MODULE`
+pub fn generated_link_require(block: &Block) -> Option<Punctuated<Expression>> {
+    let Some(LastStmt::Return(r#return)) = block.last_stmt() else {
+        return None;
+    };
+
+    if r#return.returns().len() != 1
+        || r#return.returns().iter().next()?.to_string().trim() != "REQUIRED_MODULE"
+    {
+        return None;
+    }
+
+    block.stmts().find_map(|stmt| match stmt {
+        Stmt::LocalAssignment(local_assignment)
+            if local_assignment.names().len() == 1
+                && local_assignment.names().iter().next()?.token().to_string()
+                    == "REQUIRED_MODULE" =>
+        {
+            Some(local_assignment.expressions().clone())
+        }
+        _ => None,
+    })
+}
+
+/// The names of the locals holding the required modules in links generated by this tool
+const GENERATED_LOCALS: &[&str] = &["REQUIRED_MODULE", "REQUIRED_TYPES"];
+
+/// Whether a statement of a generated link was generated by this tool, i.e. it is a local holding a required
+/// module or a re-exported type
+fn is_generated_stmt(stmt: &Stmt) -> bool {
+    let is_generated_local =
+        |name: &TokenReference| GENERATED_LOCALS.contains(&name.token().to_string().as_str());
+
+    match stmt {
+        Stmt::LocalAssignment(local_assignment) => {
+            local_assignment.names().len() == 1
+                && is_generated_local(local_assignment.names().iter().next().unwrap())
+        }
+        Stmt::ExportedTypeDeclaration(stmt) => matches!(
+            stmt.type_declaration().type_definition(),
+            TypeInfo::Module { module, .. } if is_generated_local(module)
+        ),
+        _ => false,
+    }
+}
+
+/// Splits a link into the statements which are not part of the link itself, which are kept as they are, and
+/// the leading trivia of its require, such as a header comment or a `--!strict` directive
+fn link_parts(block: &Block) -> (Vec<(Stmt, Option<TokenReference>)>, Vec<Token>) {
+    if generated_link_require(block).is_some() {
+        let mut leading_trivia = Vec::new();
+        let mut stmts = Vec::new();
+        for (stmt, semicolon) in block.stmts_with_semicolon() {
+            match stmt {
+                Stmt::LocalAssignment(local_assignment)
+                    if local_assignment
+                        .names()
+                        .iter()
+                        .next()
+                        .unwrap()
+                        .token()
+                        .to_string()
+                        == "REQUIRED_MODULE" =>
+                {
+                    leading_trivia = local_assignment
+                        .local_token()
+                        .leading_trivia()
+                        .cloned()
+                        .collect();
+                }
+                stmt if is_generated_stmt(stmt) => {}
+                _ => stmts.push((stmt.clone(), semicolon.clone())),
+            }
+        }
+
+        return (stmts, leading_trivia);
+    }
+
+    let leading_trivia = match block.last_stmt() {
+        Some(LastStmt::Return(r#return)) => r#return.token().leading_trivia().cloned().collect(),
+        _ => Vec::new(),
+    };
+    (
+        block.stmts_with_semicolon().cloned().collect(),
+        leading_trivia,
+    )
+}
+
+/// Creates a plain link of the form `return require(...)`, as generated by wally.
+/// Statements and comments which are not part of the link are kept
+pub fn restore_link(parsed_code: Ast, return_expressions: Punctuated<Expression>) -> Ast {
+    let (stmts, leading_trivia) = link_parts(parsed_code.nodes());
+    let new_nodes = parsed_code
+        .nodes()
+        .clone()
+        .with_stmts(stmts)
+        .with_last_stmt(Some((
+            LastStmt::Return(
+                Return::new()
+                    .with_token(symbol_with_leading_trivia("return ", leading_trivia))
+                    .with_returns(return_expressions),
+            ),
+            None,
+        )));
+    parsed_code.with_nodes(new_nodes)
+}
+
+/// Recovers the require expressions from a link, i.e. a module which only returns a required module
+/// (`return require(...)`), or a link previously generated by this tool
+pub fn link_require(block: &Block) -> Option<Punctuated<Expression>> {
+    if let Some(expressions) = generated_link_require(block) {
+        return Some(expressions);
+    }
+
+    match block.last_stmt() {
+        Some(LastStmt::Return(r#return)) if block.stmts().next().is_none() => {
+            Some(r#return.returns().clone())
+        }
+        _ => None,
+    }
+}
+
+/// The new link created by [`mutate_link`]
 pub enum MutateLinkResult {
-    Changed(Ast),
+    /// The new link, re-exporting the types of the module it points to
+    Changed(Box<Ast>),
+    /// The module has no exported types, so the link does not need to change
     Unchanged,
 }
 
@@ -202,23 +593,80 @@ pub fn mutate_link(
     parsed_code: Ast,
     return_expressions: Punctuated<Expression>,
     contents: &str,
+) -> Result<MutateLinkResult> {
+    create_link(parsed_code, return_expressions, None, contents)
+}
+
+/// Like [`mutate_link`], for a linked module which only forwards to another module (e.g. `return require(script.Main)`).
+/// The forwarding module exports no types itself, so they are re-exported from a `REQUIRED_TYPES` local
+/// requiring the module it ends at through `types_expressions`
+pub fn mutate_forwarding_link(
+    parsed_code: Ast,
+    return_expressions: Punctuated<Expression>,
+    types_expressions: Punctuated<Expression>,
+    contents: &str,
+) -> Result<MutateLinkResult> {
+    create_link(
+        parsed_code,
+        return_expressions,
+        Some(types_expressions),
+        contents,
+    )
+}
+
+fn create_link(
+    parsed_code: Ast,
+    return_expressions: Punctuated<Expression>,
+    types_expressions: Option<Punctuated<Expression>>,
+    contents: &str,
 ) -> Result<MutateLinkResult> {
     let type_declarations = type_declarations_from_source(contents)?;
 
     if type_declarations.is_empty() {
+        // A previously generated link may re-export types that no longer exist, so restore it
+        if generated_link_require(parsed_code.nodes()).is_some() {
+            return Ok(MutateLinkResult::Changed(Box::new(restore_link(
+                parsed_code,
+                return_expressions,
+            ))));
+        }
+
         return Ok(MutateLinkResult::Unchanged);
     }
 
+    // Statements which are not part of the link are kept, followed by the require and the re-exported types
+    let (mut stmts, leading_trivia) = link_parts(parsed_code.nodes());
+    stmts.push(extract_require_into_local_stmt(
+        "REQUIRED_MODULE",
+        return_expressions,
+        leading_trivia,
+    ));
+    match types_expressions {
+        Some(types_expressions) => {
+            stmts.push(extract_require_into_local_stmt(
+                "REQUIRED_TYPES",
+                types_expressions,
+                Vec::new(),
+            ));
+            stmts.extend(re_export_type_declarations(
+                type_declarations,
+                "REQUIRED_TYPES",
+            ));
+        }
+        None => stmts.extend(re_export_type_declarations(
+            type_declarations,
+            "REQUIRED_MODULE",
+        )),
+    }
+
     let new_nodes = parsed_code
         .nodes()
         .clone()
-        .with_stmts(
-            std::iter::once(extract_require_into_local_stmt(return_expressions))
-                .chain(re_export_type_declarations(type_declarations))
-                .collect(),
-        )
+        .with_stmts(stmts)
         .with_last_stmt(Some(create_return_require_variable()));
-    Ok(MutateLinkResult::Changed(parsed_code.with_nodes(new_nodes)))
+    Ok(MutateLinkResult::Changed(Box::new(
+        parsed_code.with_nodes(new_nodes),
+    )))
 }
 
 #[cfg(test)]
@@ -234,7 +682,8 @@ mod tests {
         let type_declarations = type_declarations_from_source(code).unwrap();
         assert_eq!(type_declarations.len(), 1);
 
-        let reexported_type_declarations = re_export_type_declarations(type_declarations);
+        let reexported_type_declarations =
+            re_export_type_declarations(type_declarations, "REQUIRED_MODULE");
         assert_eq!(reexported_type_declarations.len(), 1);
 
         assert_eq!(
@@ -252,7 +701,8 @@ mod tests {
         let type_declarations = type_declarations_from_source(code).unwrap();
         assert_eq!(type_declarations.len(), 1);
 
-        let reexported_type_declarations = re_export_type_declarations(type_declarations);
+        let reexported_type_declarations =
+            re_export_type_declarations(type_declarations, "REQUIRED_MODULE");
         assert_eq!(reexported_type_declarations.len(), 1);
 
         assert_eq!(
@@ -260,4 +710,161 @@ mod tests {
             "export type Value<T, S > = REQUIRED_MODULE.Value<T, S >"
         );
     }
+
+    #[test]
+    fn keeps_resolvable_default_generics() {
+        let code = r#"
+            type Internal = number
+            export type State = { count: number }
+            export type Value<T, A = {T}, B = T?, C = (T) -> (), D = "literal", E = number, F = nil, G = State, H = <U>(U) -> U> = Types.Value<T>
+            export type Other<T, A = Internal, B = Types.Value<T>, C = typeof(value), D = { [Internal]: T }> = Types.Other<T>
+        "#;
+
+        let type_declarations = type_declarations_from_source(code).unwrap();
+        let reexported_type_declarations =
+            re_export_type_declarations(type_declarations, "REQUIRED_MODULE");
+        assert_eq!(reexported_type_declarations.len(), 3);
+
+        assert_eq!(
+            reexported_type_declarations[1].0.to_string(),
+            r#"export type Value<T, A = {T}, B = T?, C = (T) -> (), D = "literal", E = number, F = nil, G = REQUIRED_MODULE.State, H = <U>(U) -> U> = REQUIRED_MODULE.Value<T, A , B , C , D , E , F , G , H >"#
+        );
+        assert_eq!(
+            reexported_type_declarations[2].0.to_string(),
+            "export type Other<T, A , B , C , D > = REQUIRED_MODULE.Other<T, A , B , C , D >"
+        );
+    }
+
+    #[test]
+    fn qualifies_exported_types_in_default_generics() {
+        let code = r"
+            export type State = { count: number }
+            export type Action<T> = { payload: T }
+            export type Store<S = State, A = { [string]: Action<State> }, F = <State>(State) -> State> = Types.Store<S, A, F>
+            export type Shadowed<State, S = State> = Types.Shadowed<State, S>
+        ";
+
+        let type_declarations = type_declarations_from_source(code).unwrap();
+        let reexported_type_declarations =
+            re_export_type_declarations(type_declarations, "REQUIRED_TYPES");
+
+        assert_eq!(
+            reexported_type_declarations[2].0.to_string(),
+            "export type Store<S = REQUIRED_TYPES.State, A = { [string]: REQUIRED_TYPES.Action<REQUIRED_TYPES.State> }, F = <State>(State) -> State> = REQUIRED_TYPES.Store<S , A , F >"
+        );
+        assert_eq!(
+            reexported_type_declarations[3].0.to_string(),
+            "export type Shadowed<State, S = State> = REQUIRED_TYPES.Shadowed<State, S >"
+        );
+    }
+
+    #[test]
+    fn preserves_doc_comments() {
+        let code = "--!strict\n-- License header\n\n--- A value\n--- @within Types\nexport type Value = number\n\n--[[ A state ]]\nexport type State = {}\n";
+
+        let type_declarations = type_declarations_from_source(code).unwrap();
+        let reexported_type_declarations =
+            re_export_type_declarations(type_declarations, "REQUIRED_MODULE");
+
+        assert_eq!(
+            reexported_type_declarations[0].0.to_string(),
+            "--- A value\n--- @within Types\nexport type Value = REQUIRED_MODULE.Value "
+        );
+        assert_eq!(
+            reexported_type_declarations[1].0.to_string(),
+            "--[[ A state ]]\nexport type State = REQUIRED_MODULE.State "
+        );
+    }
+
+    #[test]
+    fn recognises_links() {
+        let link_require = |code: &str| {
+            link_require(full_moon::parse(code).unwrap().nodes()).map(|returns| returns.to_string())
+        };
+
+        assert_eq!(
+            link_require("return require(script.Parent.Example)\n").as_deref(),
+            Some("require(script.Parent.Example)\n")
+        );
+        assert_eq!(
+            link_require(
+                "local REQUIRED_MODULE = require(script.Parent.Example)\nexport type T = REQUIRED_MODULE.T\nreturn REQUIRED_MODULE\n"
+            )
+            .as_deref(),
+            Some("require(script.Parent.Example)\n")
+        );
+        assert_eq!(
+            link_require("local Module = {}\nreturn require(script.Parent.Example)\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn regenerating_a_generated_link_is_idempotent() {
+        let link = "return require(script.Parent._Index['pkg']['pkg'])\n";
+        let contents = "export type Value<T> = { value: T }\n";
+
+        let mutate = |code: &str, contents: &str| {
+            let parsed_code = full_moon::parse(code).unwrap();
+            let returns = match generated_link_require(parsed_code.nodes()) {
+                Some(returns) => returns,
+                None => match parsed_code.nodes().last_stmt() {
+                    Some(LastStmt::Return(r#return)) => r#return.returns().clone(),
+                    _ => unreachable!(),
+                },
+            };
+            match mutate_link(parsed_code, returns, contents).unwrap() {
+                MutateLinkResult::Changed(ast) => ast.to_string(),
+                MutateLinkResult::Unchanged => code.to_string(),
+            }
+        };
+
+        let generated = mutate(link, contents);
+        assert!(generated_link_require(full_moon::parse(link).unwrap().nodes()).is_none());
+        assert_eq!(mutate(&generated, contents), generated);
+        assert_eq!(mutate(&generated, "return {}\n"), link);
+
+        // Header comments, directives and other statements are kept
+        let link = "--!strict\n-- Link file\nlocal _ = 1\n\n-- The package\nreturn require(script.Parent._Index['pkg']['pkg'])\n";
+        let generated = mutate(link, contents);
+        assert_eq!(
+            generated,
+            "--!strict\n-- Link file\nlocal _ = 1\n\n-- The package\nlocal REQUIRED_MODULE = require(script.Parent._Index['pkg']['pkg'])\nexport type Value<T> = REQUIRED_MODULE.Value<T>\nreturn REQUIRED_MODULE\n"
+        );
+        assert_eq!(mutate(&generated, contents), generated);
+        assert_eq!(mutate(&generated, "return {}\n"), link);
+    }
+
+    #[test]
+    fn re_exports_forwarded_types_from_the_descendant() {
+        let parsed_code = full_moon::parse("return require(script.Parent.Example)\n").unwrap();
+        let Some(LastStmt::Return(r#return)) = parsed_code.nodes().last_stmt() else {
+            unreachable!()
+        };
+        let returns = r#return.returns().clone();
+        let types_returns = match full_moon::parse("return require(script.Parent.Example.Main)\n")
+            .unwrap()
+            .nodes()
+            .last_stmt()
+        {
+            Some(LastStmt::Return(r#return)) => r#return.returns().clone(),
+            _ => unreachable!(),
+        };
+
+        let MutateLinkResult::Changed(ast) = mutate_forwarding_link(
+            parsed_code,
+            returns,
+            types_returns,
+            "export type Value = number\nreturn {}\n",
+        )
+        .unwrap() else {
+            unreachable!()
+        };
+
+        assert_eq!(
+            ast.to_string(),
+            "local REQUIRED_MODULE = require(script.Parent.Example)\nlocal REQUIRED_TYPES = require(script.Parent.Example.Main)\nexport type Value = REQUIRED_TYPES.Value \nreturn REQUIRED_MODULE\n"
+        );
+        assert!(generated_link_require(ast.nodes()).is_some());
+    }
 }
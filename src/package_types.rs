@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use full_moon::{
+    ast::LastStmt,
+    tokenizer::{Token, TokenReference, TokenType},
+};
+
+use crate::fixer::{find_thunks, ThunkResult};
+use crate::link_mutator::*;
+use crate::require_parser::is_identifier;
+
+/// The path of the type declaration file written for a link file, e.g. `PackageTypes/Promise.luau` for `Packages/Promise.lua`
+pub fn types_file_path(types_dir: &Path, thunk: &Path) -> PathBuf {
+    let name = thunk.file_stem().unwrap_or(thunk.as_os_str());
+    types_dir.join(name).with_extension("luau")
+}
+
+/// Fails if link files of different packages folders would write the same type declaration file, e.g.
+/// `Packages/Promise.lua` and `ServerPackages/Promise.lua`, since only one of them could be kept
+pub fn check_types_file_collisions(types_dir: &Path, packages_folders: &[PathBuf]) -> Result<()> {
+    let mut types_files: HashMap<PathBuf, PathBuf> = HashMap::new();
+
+    for packages_folder in packages_folders {
+        let (thunks, _) = find_thunks(packages_folder)?;
+        for thunk in thunks.into_iter().filter(|thunk| !is_index_thunk(thunk)) {
+            let canonical_thunk = thunk.canonicalize().unwrap_or_else(|_| thunk.clone());
+            match types_files.insert(types_file_path(types_dir, &thunk), canonical_thunk.clone()) {
+                Some(other) if other != canonical_thunk => bail!(
+                    "Link files '{}' and '{}' would both write the type declaration file '{}'. Use a separate \
+                     types directory for each packages folder",
+                    other.display(),
+                    thunk.display(),
+                    types_file_path(types_dir, &thunk).display()
+                ),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a link file is inside an `_Index`, i.e. it links a dependency of a package rather than a direct dependency
+pub fn is_index_thunk(thunk: &Path) -> bool {
+    thunk
+        .parent()
+        .and_then(Path::parent)
+        .and_then(Path::file_name)
+        .is_some_and(|name| name == "_Index")
+}
+
+/// The steps from `from_dir` to the module at `module`: the number of parent directories to go up, followed by the names
+/// to go down. `init` files are their directory's module. Both paths must be absolute
+fn relative_module_path(from_dir: &Path, module: &Path) -> (usize, Vec<String>) {
+    let module = match module.file_stem() {
+        Some(stem) if stem == "init" => module.parent().unwrap_or(module).to_path_buf(),
+        _ => module.with_extension(""),
+    };
+
+    let from: Vec<Component> = from_dir.components().collect();
+    let to: Vec<Component> = module.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let names = to[common..]
+        .iter()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect();
+    (from.len() - common, names)
+}
+
+/// Creates a relative string require from a file in `from_dir` to the module at `module`, e.g. `../Packages/_Index/...`.
+/// Both paths must be absolute
+fn relative_string_require(from_dir: &Path, module: &Path) -> String {
+    let (parents, names) = relative_module_path(from_dir, module);
+
+    let mut components = vec![String::from(".."); parents];
+    if components.is_empty() {
+        components.push(String::from("."));
+    }
+    components.extend(names);
+
+    components.join("/")
+}
+
+/// Creates an instance require from a module in `from_dir` to the module at `module`, e.g. `script.Parent.Promise`,
+/// for directories which mirror the instance tree. Both paths must be absolute
+fn relative_instance_require(from_dir: &Path, module: &Path) -> String {
+    let (parents, names) = relative_module_path(from_dir, module);
+
+    let mut require = String::from("script.Parent");
+    require.push_str(&".Parent".repeat(parents));
+    for name in names {
+        if is_identifier(&name) {
+            require.push_str(&format!(".{name}"));
+        } else {
+            require.push_str(&format!("[{name:?}]"));
+        }
+    }
+
+    require
+}
+
+/// Resolves the types directory to a canonical path, even if it does not exist yet
+fn canonical_types_dir(types_dir: &Path) -> Result<PathBuf> {
+    if let Ok(types_dir) = types_dir.canonicalize() {
+        return Ok(types_dir);
+    }
+
+    let types_dir = std::path::absolute(types_dir).context("Failed to resolve types directory")?;
+    match (types_dir.parent(), types_dir.file_name()) {
+        (Some(parent), Some(name)) => Ok(parent
+            .canonicalize()
+            .unwrap_or_else(|_| parent.to_path_buf())
+            .join(name)),
+        _ => Ok(types_dir),
+    }
+}
+
+/// Creates the contents of a type declaration file in `types_dir`, re-exporting the types of the module at `module`
+pub fn types_file_contents(
+    types_dir: &Path,
+    module: &Path,
+    contents: &str,
+) -> Result<MutateLinkResult> {
+    let types_dir = canonical_types_dir(types_dir)?;
+    let module = module
+        .canonicalize()
+        .context("Failed to resolve linked file")?;
+
+    let code = format!(
+        "return require({:?})\n",
+        relative_string_require(&types_dir, &module)
+    );
+    let parsed_code = match full_moon::parse(&code) {
+        Ok(parsed_code) => parsed_code,
+        Err(_) => bail!("could not create require expression '{}'", code.trim()),
+    };
+    let Some(LastStmt::Return(r#return)) = parsed_code.nodes().last_stmt() else {
+        unreachable!()
+    };
+    let returns = r#return.returns().clone();
+
+    mutate_link(parsed_code, returns, contents)
+}
+
+/// Adds an alias named after `types_dir` to the `.luaurc` file next to it, so that its type declaration files can be
+/// required as e.g. `require("@PackageTypes/Promise")`. Returns whether the file was changed
+pub fn write_luaurc_alias(types_dir: &Path) -> Result<bool> {
+    let types_dir = canonical_types_dir(types_dir)?;
+    let (Some(parent), Some(name)) = (types_dir.parent(), types_dir.file_name()) else {
+        bail!("Types directory '{}' has no parent", types_dir.display());
+    };
+    let name = name.to_string_lossy().to_string();
+    let luaurc_path = parent.join(".luaurc");
+
+    let mut luaurc = match std::fs::read_to_string(&luaurc_path) {
+        Ok(contents) => serde_json::from_str::<serde_json::Value>(&contents)
+            .with_context(|| format!("Failed to parse '{}'", luaurc_path.display()))?,
+        Err(_) => serde_json::json!({}),
+    };
+
+    let Some(luaurc_object) = luaurc.as_object_mut() else {
+        bail!("'{}' is not a JSON object", luaurc_path.display());
+    };
+    let aliases = luaurc_object
+        .entry("aliases")
+        .or_insert_with(|| serde_json::json!({}));
+    let Some(aliases) = aliases.as_object_mut() else {
+        bail!(
+            "Aliases in '{}' are not a JSON object",
+            luaurc_path.display()
+        );
+    };
+
+    let alias_path = serde_json::Value::String(format!("./{name}"));
+    if aliases.get(&name) == Some(&alias_path) {
+        return Ok(false);
+    }
+    aliases.insert(name, alias_path);
+
+    std::fs::write(
+        &luaurc_path,
+        serde_json::to_string_pretty(&luaurc).unwrap() + "\n",
+    )
+    .with_context(|| format!("Failed to write '{}'", luaurc_path.display()))?;
+
+    Ok(true)
+}
+
+/// The default naming of types in the aggregated types module, e.g. `Promise_Promise`
+pub const DEFAULT_TYPES_MODULE_NAMING: &str = "{package}_{type}";
+
+/// Converts a package name into a valid identifier, e.g. `my-package` into `my_package`
+fn package_identifier(name: &str) -> String {
+    let identifier: String = name
+        .chars()
+        .map(|char| {
+            if char.is_ascii_alphanumeric() {
+                char
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if identifier.starts_with(|char: char| char.is_ascii_digit()) {
+        format!("_{identifier}")
+    } else {
+        identifier
+    }
+}
+
+/// Creates the contents of a module at `types_module` re-exporting the types of every direct dependency
+/// fixed in `results`, with each type named after `naming`, in which `{package}` and `{type}` are replaced
+/// with the package and type names. Fails if two types or packages end up with the same name.
+///
+/// The types are required through the link files, which re-export them and resolve in the instance tree at runtime,
+/// so the module should be next to them. With a types directory, its type declaration files are required instead
+pub fn types_module_contents(
+    types_module: &Path,
+    results: &[&ThunkResult],
+    naming: &str,
+    types_dir: Option<&Path>,
+) -> Result<String> {
+    if !naming.contains("{type}") {
+        bail!("Types module naming '{naming}' does not contain '{{type}}'");
+    }
+
+    // A bare file name has an empty parent, which is the current directory
+    let types_module_dir = match types_module.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    let types_module_dir = canonical_types_dir(types_module_dir)?;
+
+    let mut contents = String::new();
+    let mut package_names: HashMap<String, &Path> = HashMap::new();
+    let mut type_names: HashMap<String, &Path> = HashMap::new();
+
+    let mut results: Vec<&ThunkResult> = results
+        .iter()
+        .filter(|result| !is_index_thunk(&result.path) && !result.exported_types.is_empty())
+        .copied()
+        .collect();
+    results.sort_by_key(|result| result.path.file_stem().map(|name| name.to_os_string()));
+
+    for result in results {
+        let Some(linked_file) = &result.linked_file else {
+            continue;
+        };
+        let package = result
+            .path
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+
+        let local_name = package_identifier(&package);
+        if let Some(other) = package_names.insert(local_name.clone(), &result.path) {
+            bail!(
+                "Packages '{}' and '{}' both use the name '{local_name}' in the types module",
+                other.display(),
+                result.path.display()
+            );
+        }
+
+        let module = linked_file
+            .canonicalize()
+            .context("Failed to resolve linked file")?;
+        let module_contents =
+            std::fs::read_to_string(&module).context("Failed to read linked file")?;
+        let type_declarations = type_declarations_from_source(&module_contents)?;
+        let exported_types: Vec<String> = type_declarations
+            .iter()
+            .map(|stmt| stmt.type_declaration().type_name().token().to_string())
+            .collect();
+
+        let require = match types_dir {
+            Some(types_dir) => format!(
+                "{:?}",
+                relative_string_require(
+                    &types_module_dir,
+                    &types_file_path(&canonical_types_dir(types_dir)?, &result.path)
+                )
+            ),
+            None => relative_instance_require(
+                &types_module_dir,
+                &result
+                    .path
+                    .canonicalize()
+                    .context("Failed to resolve link file")?,
+            ),
+        };
+        contents.push_str(&format!("local {local_name} = require({require})\n"));
+
+        for (stmt, type_name) in type_declarations.iter().zip(&exported_types) {
+            let name = naming
+                .replace("{package}", &local_name)
+                .replace("{type}", type_name);
+            if !name
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || char == '_')
+            {
+                bail!("Types module naming '{naming}' creates the invalid type name '{name}'");
+            }
+            if let Some(other) = type_names.insert(name.clone(), &result.path) {
+                bail!(
+                    "Type '{name}' of package '{}' collides with a type of package '{}' in the types module",
+                    result.path.display(),
+                    other.display()
+                );
+            }
+
+            let new_stmt = create_new_type_declaration(stmt, &local_name, &exported_types);
+            let type_declaration =
+                new_stmt
+                    .type_declaration()
+                    .clone()
+                    .with_type_name(TokenReference::new(
+                        vec![],
+                        Token::new(TokenType::Identifier {
+                            identifier: name.into(),
+                        }),
+                        new_stmt
+                            .type_declaration()
+                            .type_name()
+                            .trailing_trivia()
+                            .cloned()
+                            .collect(),
+                    ));
+            contents.push_str(&new_stmt.with_type_declaration(type_declaration).to_string());
+            contents.push('\n');
+        }
+    }
+
+    contents.push_str("return {}\n");
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn creates_relative_string_requires() {
+        assert_eq!(
+            relative_string_require(
+                Path::new("/project/PackageTypes"),
+                Path::new("/project/Packages/_Index/acme_promise@1.0.0/promise/init.lua")
+            ),
+            "../Packages/_Index/acme_promise@1.0.0/promise"
+        );
+        assert_eq!(
+            relative_string_require(
+                Path::new("/project/PackageTypes"),
+                Path::new("/project/PackageTypes/Local/Module.luau")
+            ),
+            "./Local/Module"
+        );
+        assert_eq!(
+            relative_instance_require(
+                Path::new("/project/src"),
+                Path::new("/project/Packages/_Index/acme_promise@1.0.0/promise/init.lua")
+            ),
+            "script.Parent.Parent.Packages._Index[\"acme_promise@1.0.0\"].promise"
+        );
+        assert_eq!(
+            relative_instance_require(
+                Path::new("/project/Packages"),
+                Path::new("/project/Packages/Promise.lua")
+            ),
+            "script.Parent.Promise"
+        );
+        assert!(is_index_thunk(Path::new(
+            "Packages/_Index/acme_promise@1.0.0/Other.lua"
+        )));
+        assert!(!is_index_thunk(Path::new("Packages/Promise.lua")));
+    }
+
+    #[test]
+    fn detects_type_declaration_files_shared_by_packages_folders() {
+        let root = TempDir::new("types-file-collisions");
+        for packages_folder in ["Packages", "ServerPackages"] {
+            std::fs::create_dir_all(root.join(packages_folder).join("_Index/pkg")).unwrap();
+            std::fs::write(root.join(packages_folder).join("_Index/pkg/Util.lua"), "").unwrap();
+        }
+        std::fs::write(root.join("Packages/Promise.lua"), "").unwrap();
+        std::fs::write(root.join("ServerPackages/Signal.lua"), "").unwrap();
+
+        let types_dir = root.join("PackageTypes");
+        let packages_folders = [root.join("Packages"), root.join("ServerPackages")];
+        check_types_file_collisions(&types_dir, &packages_folders).unwrap();
+        // The same folder passed twice writes the same files
+        check_types_file_collisions(&types_dir, &[root.join("Packages"), root.join("Packages")])
+            .unwrap();
+
+        std::fs::write(root.join("ServerPackages/Promise.lua"), "").unwrap();
+        assert!(check_types_file_collisions(&types_dir, &packages_folders).is_err());
+    }
+
+    #[test]
+    fn aggregates_package_types_with_collision_detection() {
+        let root = TempDir::new("types-module");
+        std::fs::create_dir_all(root.join("Packages/_Index/promise")).unwrap();
+        std::fs::create_dir_all(root.join("Packages/_Index/signal")).unwrap();
+        std::fs::write(
+            root.join("Packages/_Index/promise/init.lua"),
+            "export type Status = string\nexport type Promise<T = Status> = {}\nreturn {}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("Packages/_Index/signal/init.lua"),
+            "export type Status = boolean\nreturn {}\n",
+        )
+        .unwrap();
+
+        std::fs::write(root.join("Packages/Promise.lua"), "").unwrap();
+        std::fs::write(root.join("Packages/Signal.lua"), "").unwrap();
+
+        let result = |name: &str, package: &str, exported_types: &[&str]| ThunkResult {
+            path: root.join("Packages").join(name),
+            require_path: None,
+            linked_file: Some(root.join("Packages/_Index").join(package).join("init.lua")),
+            exported_types: exported_types.iter().map(|name| name.to_string()).collect(),
+            change: None,
+            outcome: crate::fixer::ThunkOutcome::Successful,
+        };
+        let results = [
+            result("Signal.lua", "signal", &["Status"]),
+            result("Promise.lua", "promise", &["Status", "Promise"]),
+        ];
+        let results: Vec<&ThunkResult> = results.iter().collect();
+        let types_module = root.join("Packages/Types.luau");
+
+        assert_eq!(
+            types_module_contents(&types_module, &results, DEFAULT_TYPES_MODULE_NAMING, None)
+                .unwrap(),
+            "local Promise = require(script.Parent.Promise)\nexport type Promise_Status = Promise.Status \nexport type Promise_Promise<T = Promise.Status> = Promise.Promise<T >\nlocal Signal = require(script.Parent.Signal)\nexport type Signal_Status = Signal.Status \nreturn {}\n"
+        );
+        assert!(types_module_contents(
+            &root.join("Types.luau"),
+            &results,
+            DEFAULT_TYPES_MODULE_NAMING,
+            Some(&root.join("PackageTypes"))
+        )
+        .unwrap()
+        .starts_with("local Promise = require(\"./PackageTypes/Promise\")\n"));
+        assert!(types_module_contents(&types_module, &results, "{type}", None).is_err());
+        assert!(types_module_contents(&types_module, &results, "{package}", None).is_err());
+    }
+
+    #[test]
+    fn adds_the_types_alias_keeping_luaurc_order() {
+        let root = TempDir::new("luaurc-alias");
+        let types_dir = root.join("PackageTypes");
+        std::fs::write(
+            root.join(".luaurc"),
+            r#"{"languageMode": "strict", "aliases": {"Shared": "./src/shared"}, "lint": {"*": true}}"#,
+        )
+        .unwrap();
+
+        assert!(write_luaurc_alias(&types_dir).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(root.join(".luaurc")).unwrap(),
+            "{\n  \"languageMode\": \"strict\",\n  \"aliases\": {\n    \"Shared\": \"./src/shared\",\n    \"PackageTypes\": \"./PackageTypes\"\n  },\n  \"lint\": {\n    \"*\": true\n  }\n}\n"
+        );
+        assert!(!write_luaurc_alias(&types_dir).unwrap());
+    }
+}
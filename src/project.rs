@@ -0,0 +1,386 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+
+use crate::sourcemap::SourcemapNode;
+
+const SCRIPT_EXTENSIONS: &[&str] = &["luau", "lua"];
+
+/// The suffixes of script file names with the class of their instance, e.g. `Main.server.luau` is a `Script`
+const SCRIPT_SUFFIXES: &[(&str, &str)] = &[
+    (".server", "Script"),
+    (".client", "LocalScript"),
+    ("", "ModuleScript"),
+];
+
+/// Splits the name of a script file into its instance name and class, e.g. `("Main", "Script")` for `Main.server.luau`
+fn script_file(file_name: &str) -> Option<(&str, &'static str)> {
+    let stem = SCRIPT_EXTENSIONS
+        .iter()
+        .find_map(|extension| file_name.strip_suffix(&format!(".{extension}")))?;
+
+    SCRIPT_SUFFIXES
+        .iter()
+        .find_map(|(suffix, class_name)| Some((stem.strip_suffix(suffix)?, *class_name)))
+}
+
+/// Reads the `className` of a `.meta.json` file, if it exists
+fn meta_class_name(path: &Path) -> Result<Option<String>> {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return Ok(None);
+    };
+    let meta: Value = serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+
+    Ok(meta
+        .get("className")
+        .and_then(Value::as_str)
+        .map(String::from))
+}
+
+/// Applies a `.meta.json` file to a node, if it exists
+fn apply_meta_file(node: &mut SourcemapNode, path: PathBuf) -> Result<()> {
+    if !path.is_file() {
+        return Ok(());
+    }
+
+    if let Some(class_name) = meta_class_name(&path)? {
+        node.class_name = class_name;
+    }
+    node.file_paths.push(path);
+
+    Ok(())
+}
+
+fn read_project(project_path: &Path) -> Result<Value> {
+    let contents = std::fs::read_to_string(project_path)
+        .with_context(|| format!("Failed to read project '{}'", project_path.display()))?;
+    serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse project '{}'", project_path.display()))
+}
+
+fn project_tree<'a>(project: &'a Value, project_path: &Path) -> Result<&'a Map<String, Value>> {
+    project
+        .get("tree")
+        .and_then(Value::as_object)
+        .with_context(|| format!("Project '{}' has no tree", project_path.display()))
+}
+
+/// The `$path` of a project node, and whether it is optional
+fn node_path<'a>(
+    name: &str,
+    project_node: &'a Map<String, Value>,
+) -> Result<Option<(&'a str, bool)>> {
+    match project_node.get("$path") {
+        Some(Value::String(path)) => Ok(Some((path, false))),
+        Some(Value::Object(path)) => match path.get("optional") {
+            Some(Value::String(path)) => Ok(Some((path, true))),
+            _ => bail!("Invalid $path of '{name}'"),
+        },
+        Some(_) => bail!("Invalid $path of '{name}'"),
+        None => Ok(None),
+    }
+}
+
+/// The file or directory the root of a project points to with its `$path`, e.g. `lib` for the
+/// `{"tree": {"$path": "lib"}}` project shipped with most wally packages
+pub fn project_root_path(project_path: &Path) -> Result<Option<PathBuf>> {
+    let project = read_project(project_path)?;
+    let tree = project_tree(&project, project_path)?;
+
+    Ok(node_path("tree", tree)?
+        .map(|(path, _)| project_path.parent().unwrap_or(Path::new("")).join(path)))
+}
+
+/// Builds sourcemap nodes from a Rojo project the way `rojo sourcemap` does, supporting the subset of Rojo needed to
+/// resolve package requires: `$path`, `$className`, nested trees and projects, script files and `.meta.json` files
+struct ProjectResolver {
+    /// The projects currently being read, to detect projects which include themselves
+    projects: Vec<PathBuf>,
+}
+
+impl ProjectResolver {
+    fn snapshot_project(
+        &mut self,
+        project_path: &Path,
+        name: Option<&str>,
+    ) -> Result<SourcemapNode> {
+        let canonical_project_path = project_path
+            .canonicalize()
+            .with_context(|| format!("Project '{}' not found", project_path.display()))?;
+        if self.projects.contains(&canonical_project_path) {
+            bail!("Project '{}' includes itself", project_path.display());
+        }
+
+        let project = read_project(project_path)?;
+
+        let name = match name {
+            Some(name) => name,
+            None => project
+                .get("name")
+                .and_then(Value::as_str)
+                .with_context(|| format!("Project '{}' has no name", project_path.display()))?,
+        };
+        let tree = project_tree(&project, project_path)?;
+
+        self.projects.push(canonical_project_path);
+        let node =
+            self.snapshot_project_node(name, tree, project_path.parent().unwrap_or(Path::new("")));
+        self.projects.pop();
+
+        let mut node = node?.with_context(|| {
+            format!(
+                "Optional $path of project '{}' not found",
+                project_path.display()
+            )
+        })?;
+        node.file_paths.push(project_path.to_path_buf());
+        Ok(node)
+    }
+
+    fn snapshot_project_node(
+        &mut self,
+        name: &str,
+        project_node: &Map<String, Value>,
+        project_dir: &Path,
+    ) -> Result<Option<SourcemapNode>> {
+        let path = node_path(name, project_node)?;
+
+        // An optional path which does not exist leaves out the whole instance
+        let node = match path {
+            Some((path, optional)) => {
+                let path = project_dir.join(path);
+                match self.snapshot_path(&path, Some(name))? {
+                    Some(node) => Some(node),
+                    None if optional => return Ok(None),
+                    None => bail!("$path '{}' of '{name}' not found", path.display()),
+                }
+            }
+            None => None,
+        };
+
+        let mut node = node.unwrap_or_else(|| SourcemapNode {
+            name: name.to_string(),
+            // Services are usually declared without a class name
+            class_name: name.to_string(),
+            file_paths: Vec::new(),
+            children: Vec::new(),
+        });
+
+        if let Some(class_name) = project_node.get("$className").and_then(Value::as_str) {
+            node.class_name = class_name.to_string();
+        }
+
+        for (child_name, child) in project_node {
+            if child_name.starts_with('$') {
+                continue;
+            }
+            let Some(child) = child.as_object() else {
+                bail!("Invalid tree node '{child_name}' in '{name}'");
+            };
+
+            let Some(child) = self.snapshot_project_node(child_name, child, project_dir)? else {
+                continue;
+            };
+            node.children.retain(|existing| existing.name != child.name);
+            node.children.push(child);
+        }
+
+        Ok(Some(node))
+    }
+
+    /// Builds the node of a file or directory, or `None` if it does not exist or does not become an instance.
+    /// The node is named `name` if given, as for the `$path` of a project node, and after the file otherwise
+    fn snapshot_path(&mut self, path: &Path, name: Option<&str>) -> Result<Option<SourcemapNode>> {
+        let file_name = path
+            .file_name()
+            .context("Path has no file name")?
+            .to_string_lossy()
+            .to_string();
+
+        if path.is_dir() {
+            return self
+                .snapshot_dir(path, name.unwrap_or(&file_name))
+                .map(Some);
+        }
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        if let Some(project_name) = file_name.strip_suffix(".project.json") {
+            return self
+                .snapshot_project(path, Some(name.unwrap_or(project_name)))
+                .map(Some);
+        }
+        let Some((script_name, class_name)) = script_file(&file_name) else {
+            return Ok(None);
+        };
+
+        let mut node = SourcemapNode {
+            name: name.unwrap_or(script_name).to_string(),
+            class_name: class_name.to_string(),
+            file_paths: vec![path.to_path_buf()],
+            children: Vec::new(),
+        };
+        apply_meta_file(
+            &mut node,
+            path.with_file_name(format!("{script_name}.meta.json")),
+        )?;
+
+        Ok(Some(node))
+    }
+
+    fn snapshot_dir(&mut self, path: &Path, name: &str) -> Result<SourcemapNode> {
+        let project_path = path.join("default.project.json");
+        if project_path.is_file() {
+            return self.snapshot_project(&project_path, Some(name));
+        }
+
+        let mut node = SourcemapNode {
+            name: name.to_string(),
+            class_name: "Folder".to_string(),
+            file_paths: Vec::new(),
+            children: Vec::new(),
+        };
+
+        let mut entries = std::fs::read_dir(path)
+            .with_context(|| format!("Failed to read directory '{}'", path.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()
+            .with_context(|| format!("Failed to read directory '{}'", path.display()))?;
+        entries.sort();
+
+        // A directory with an `init` script becomes that script, with the other files as its children
+        for entry in &entries {
+            let file_name = entry.file_name().unwrap_or_default().to_string_lossy();
+            if let Some(("init", class_name)) = script_file(&file_name) {
+                node.class_name = class_name.to_string();
+                node.file_paths.push(entry.clone());
+                break;
+            }
+        }
+        apply_meta_file(&mut node, path.join("init.meta.json"))?;
+
+        for entry in &entries {
+            let file_name = entry.file_name().unwrap_or_default().to_string_lossy();
+            if file_name.ends_with(".meta.json")
+                || matches!(script_file(&file_name), Some(("init", _)))
+            {
+                continue;
+            }
+
+            if let Some(child) = self.snapshot_path(entry, None)? {
+                node.children.push(child);
+            }
+        }
+
+        Ok(node)
+    }
+}
+
+/// Builds a sourcemap from a Rojo project file (e.g. `default.project.json`) without running `rojo sourcemap`.
+/// Only the subset of Rojo needed to resolve package requires is supported. File paths are relative to the current
+/// directory, like those of a sourcemap generated in it
+pub fn sourcemap_from_project(project_path: &Path) -> Result<SourcemapNode> {
+    ProjectResolver {
+        projects: Vec::new(),
+    }
+    .snapshot_project(project_path, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn builds_sourcemap_from_project() {
+        let root = TempDir::new("project");
+        let package = root.join("Packages/_Index/acme_promise@1.0.0/promise");
+        std::fs::create_dir_all(package.join("src/Util")).unwrap();
+        std::fs::write(
+            root.join("default.project.json"),
+            r#"{"name": "game", "tree": {"$className": "DataModel", "ReplicatedStorage": {"Packages": {"$path": "Packages"}, "Shared": {"$path": "shared.project.json"}, "Missing": {"$path": {"optional": "Missing"}}}}}"#,
+        )
+        .unwrap();
+        std::fs::write(root.join("Packages/Promise.lua"), "").unwrap();
+        std::fs::write(
+            root.join("shared.project.json"),
+            r#"{"name": "shared", "tree": {"$path": "Util.lua"}}"#,
+        )
+        .unwrap();
+        std::fs::write(root.join("Util.lua"), "").unwrap();
+        std::fs::write(
+            package.join("default.project.json"),
+            r#"{"name": "acme/promise", "tree": {"$path": "src"}}"#,
+        )
+        .unwrap();
+        std::fs::write(package.join("src/init.luau"), "").unwrap();
+        std::fs::write(package.join("src/Main.server.luau"), "").unwrap();
+        std::fs::write(package.join("src/Util/init.lua"), "").unwrap();
+        std::fs::write(
+            package.join("src/Util/init.meta.json"),
+            r#"{"className": "Configuration"}"#,
+        )
+        .unwrap();
+
+        // A package made of a single file is named after the directory containing its project
+        let single = root.join("Packages/_Index/acme_single@1.0.0/single");
+        std::fs::create_dir_all(&single).unwrap();
+        std::fs::write(
+            single.join("default.project.json"),
+            r#"{"name": "single", "tree": {"$path": "Single.lua"}}"#,
+        )
+        .unwrap();
+        std::fs::write(single.join("Single.lua"), "").unwrap();
+
+        let sourcemap = sourcemap_from_project(&root.join("default.project.json")).unwrap();
+        assert_eq!(
+            (sourcemap.name.as_str(), sourcemap.class_name.as_str()),
+            ("game", "DataModel")
+        );
+
+        let replicated_storage = &sourcemap.children[0];
+        assert_eq!(replicated_storage.class_name, "ReplicatedStorage");
+        assert_eq!(
+            replicated_storage
+                .children
+                .iter()
+                .map(|child| (child.name.as_str(), child.class_name.as_str()))
+                .collect::<Vec<_>>(),
+            [("Packages", "Folder"), ("Shared", "ModuleScript")]
+        );
+
+        let packages = &replicated_storage.children[0];
+        assert_eq!(
+            packages
+                .children
+                .iter()
+                .map(|child| (child.name.as_str(), child.class_name.as_str()))
+                .collect::<Vec<_>>(),
+            [("Promise", "ModuleScript"), ("_Index", "Folder")]
+        );
+
+        let single = &packages.children[1].children[1].children[0];
+        assert_eq!(
+            (single.name.as_str(), single.class_name.as_str()),
+            ("single", "ModuleScript")
+        );
+
+        let promise = &packages.children[1].children[0].children[0];
+        assert_eq!(
+            (promise.name.as_str(), promise.class_name.as_str()),
+            ("promise", "ModuleScript")
+        );
+        assert_eq!(promise.file_paths[0], package.join("src/init.luau"));
+        assert_eq!(
+            promise
+                .children
+                .iter()
+                .map(|child| (child.name.as_str(), child.class_name.as_str()))
+                .collect::<Vec<_>>(),
+            [("Main", "Script"), ("Util", "Configuration")]
+        );
+    }
+}
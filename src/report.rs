@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::fixer::*;
+use crate::require_parser::*;
+
+/// Supported formats for run reports
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ThunkRecord<'a> {
+    path: &'a Path,
+    /// The require path as written, e.g. `script/Parent/_Index/...` or `./_Index/...`
+    require: Option<String>,
+    /// The components of an instance require path
+    require_components: Option<Vec<String>>,
+    linked_file: Option<&'a Path>,
+    exported_types: &'a [String],
+    outcome: &'static str,
+    /// The error and all of its causes, outermost first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<Vec<String>>,
+}
+
+impl<'a> From<&'a ThunkResult> for ThunkRecord<'a> {
+    fn from(result: &'a ThunkResult) -> Self {
+        let (outcome, error) = match &result.outcome {
+            ThunkOutcome::Successful => ("Successful", None),
+            ThunkOutcome::Unchanged => ("Unchanged", None),
+            ThunkOutcome::OutOfDate => ("OutOfDate", None),
+            ThunkOutcome::FailedToParseReturnStmt => ("FailedToParseReturnStmt", None),
+            ThunkOutcome::Error(err) => (
+                "Error",
+                Some(err.chain().map(|cause| cause.to_string()).collect()),
+            ),
+        };
+
+        ThunkRecord {
+            path: &result.path,
+            require: result.require_path.as_ref().map(|path| path.to_string()),
+            require_components: match &result.require_path {
+                Some(RequirePath::Instance(components)) => Some(
+                    components
+                        .iter()
+                        .map(|component| component.to_string())
+                        .collect(),
+                ),
+                _ => None,
+            },
+            linked_file: result.linked_file.as_deref(),
+            exported_types: &result.exported_types,
+            outcome,
+            error,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PackagesFolderRecord<'a> {
+    path: &'a Path,
+    success: bool,
+    thunks: Vec<ThunkRecord<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Report<'a> {
+    success: bool,
+    packages_folders: Vec<PackagesFolderRecord<'a>>,
+}
+
+/// Renders a report of a run, with one record per link file
+pub fn render_report(format: ReportFormat, results: &[PackagesFolderResult]) -> String {
+    let report = Report {
+        success: results.iter().all(PackagesFolderResult::is_success),
+        packages_folders: results
+            .iter()
+            .map(|folder| PackagesFolderRecord {
+                path: &folder.path,
+                success: folder.is_success(),
+                thunks: folder.thunks.iter().map(ThunkRecord::from).collect(),
+            })
+            .collect(),
+    };
+
+    match format {
+        ReportFormat::Json => serde_json::to_string_pretty(&report).unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use anyhow::{anyhow, Context};
+
+    use super::*;
+
+    #[test]
+    fn renders_json_records_per_thunk() {
+        let error = Err::<(), _>(anyhow!("Failed to read linked file"))
+            .context("Could not convert require expression to file path")
+            .unwrap_err();
+
+        let results = vec![PackagesFolderResult {
+            path: PathBuf::from("Packages"),
+            complete: true,
+            thunks: vec![
+                ThunkResult {
+                    path: PathBuf::from("Packages/Promise.lua"),
+                    require_path: Some(RequirePath::Instance(vec![
+                        PathComponent::Child("script".to_string()),
+                        PathComponent::Child("Parent".to_string()),
+                    ])),
+                    linked_file: Some(PathBuf::from("Packages/_Index/promise/init.lua")),
+                    exported_types: vec!["Promise".to_string()],
+                    change: None,
+                    outcome: ThunkOutcome::Successful,
+                },
+                ThunkResult {
+                    path: PathBuf::from("Packages/Broken.lua"),
+                    require_path: None,
+                    linked_file: None,
+                    exported_types: Vec::new(),
+                    change: None,
+                    outcome: ThunkOutcome::Error(error),
+                },
+            ],
+        }];
+
+        let report: serde_json::Value =
+            serde_json::from_str(&render_report(ReportFormat::Json, &results)).unwrap();
+
+        assert_eq!(report["success"], false);
+        let thunks = &report["packagesFolders"][0]["thunks"];
+        assert_eq!(thunks[0]["outcome"], "Successful");
+        assert_eq!(
+            thunks[0]["requireComponents"],
+            serde_json::json!(["script", "Parent"])
+        );
+        assert_eq!(thunks[0]["exportedTypes"], serde_json::json!(["Promise"]));
+        assert_eq!(thunks[1]["outcome"], "Error");
+        assert_eq!(
+            thunks[1]["error"],
+            serde_json::json!([
+                "Could not convert require expression to file path",
+                "Failed to read linked file"
+            ])
+        );
+    }
+}
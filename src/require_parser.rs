@@ -1,27 +1,94 @@
 use anyhow::{bail, Result};
 use full_moon::{
-    ast::{Call, Expression, FunctionArgs, Index, Suffix, Var},
+    ast::{Call, Expression, FunctionArgs, Index, LastStmt, MethodCall, Suffix, Var},
     tokenizer::TokenType,
 };
 
-/// Decomposes a VarExpression into a list of string components
-pub fn expression_to_components(expression: &Expression) -> Result<Vec<String>> {
+/// A single component of an instance path
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PathComponent {
+    /// A named child, e.g. `.Example`, `['Example']` or `:WaitForChild('Example')`. Also used for `script`, `game` and `Parent`
+    Child(String),
+    /// The closest ancestor with the given name, from `:FindFirstAncestor('Example')`
+    Ancestor(String),
+}
+
+impl std::fmt::Display for PathComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathComponent::Child(name) => write!(f, "{name}"),
+            PathComponent::Ancestor(name) => write!(f, "FindFirstAncestor({name})"),
+        }
+    }
+}
+
+impl PartialEq<&str> for PathComponent {
+    fn eq(&self, other: &&str) -> bool {
+        matches!(self, PathComponent::Child(name) if name == other)
+    }
+}
+
+/// Extracts the name and single string argument of a method call, e.g. `:GetService("Players")`
+fn method_call_with_string_argument(method_call: &MethodCall) -> Result<(String, String)> {
+    let method = method_call.name().token().to_string();
+
+    let argument = match method_call.args() {
+        FunctionArgs::Parentheses { arguments, .. } if arguments.len() == 1 => {
+            match arguments.iter().next().unwrap() {
+                Expression::String(token) => token,
+                _ => bail!("require expression not supported: method call `:{method}` must have a single string argument"),
+            }
+        }
+        FunctionArgs::String(token) => token,
+        _ => bail!("require expression not supported: method call `:{method}` must have a single string argument"),
+    };
+
+    let TokenType::StringLiteral { literal, .. } = argument.token_type() else {
+        bail!("require expression not supported: method call `:{method}` must have a single string argument")
+    };
+
+    Ok((method, literal.to_string()))
+}
+
+/// Decomposes a VarExpression (or a call chain such as `game:GetService('Players')`) into a list of path components
+pub fn expression_to_components(expression: &Expression) -> Result<Vec<PathComponent>> {
     let mut components = Vec::new();
 
-    let Expression::Var(Var::Expression(var_expression)) = expression else {
-        bail!("require expression not supported: expression must contain components of form `.value` or `['value']`")
+    let (prefix, suffixes): (_, Vec<&Suffix>) = match expression {
+        Expression::Var(Var::Expression(var_expression)) => (
+            var_expression.prefix(),
+            var_expression.suffixes().collect(),
+        ),
+        Expression::FunctionCall(call) => (call.prefix(), call.suffixes().collect()),
+        _ => bail!("require expression not supported: expression must contain components of form `.value` or `['value']`"),
     };
 
-    components.push(var_expression.prefix().to_string().trim().to_string());
+    components.push(PathComponent::Child(prefix.to_string().trim().to_string()));
 
-    for suffix in var_expression.suffixes() {
-        let Suffix::Index(index) = suffix else {
-            bail!("require expression not supported: expression must contain components of form `.value` or `['value']`")
+    for suffix in suffixes {
+        let index = match suffix {
+            Suffix::Index(index) => index,
+            Suffix::Call(Call::MethodCall(method_call)) => {
+                let (method, argument) = method_call_with_string_argument(method_call)?;
+                match method.as_str() {
+                    // Services are the top-level children of the DataModel
+                    "GetService" if components == ["game"] => {
+                        components.push(PathComponent::Child(argument))
+                    }
+                    "WaitForChild" | "FindFirstChild" => {
+                        components.push(PathComponent::Child(argument))
+                    }
+                    "FindFirstAncestor" => components.push(PathComponent::Ancestor(argument)),
+                    _ => bail!("require expression not supported: method call `:{method}` is not supported here"),
+                }
+                continue;
+            }
+            _ => bail!("require expression not supported: expression must contain components of form `.value` or `['value']`"),
         };
 
         match index {
             Index::Dot { name, .. } => {
-                components.push(name.to_string().trim().to_string());
+                components.push(PathComponent::Child(name.to_string().trim().to_string()));
             }
             Index::Brackets { expression, .. } => {
                 let Expression::String(name) = expression else {
@@ -30,7 +97,7 @@ pub fn expression_to_components(expression: &Expression) -> Result<Vec<String>>
                 let TokenType::StringLiteral { literal, .. } = name.token_type() else {
                     bail!("require expression not supported: expression contains brackets component not of the form ['value']")
                 };
-                components.push(literal.trim().to_string());
+                components.push(PathComponent::Child(literal.trim().to_string()));
             }
             _ => unreachable!(),
         }
@@ -39,7 +106,46 @@ pub fn expression_to_components(expression: &Expression) -> Result<Vec<String>>
     Ok(components)
 }
 
-pub fn match_require(expression: &Expression) -> Result<Vec<String>> {
+/// The target of a require expression
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RequirePath {
+    /// An instance path, e.g. `script.Parent.Example` as `['script', 'Parent', 'Example']`
+    Instance(Vec<PathComponent>),
+    /// A Luau string require, e.g. `"./Example"` or `"@pkg/Example"`
+    String(String),
+}
+
+impl std::fmt::Display for RequirePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequirePath::Instance(components) => write!(
+                f,
+                "{}",
+                components
+                    .iter()
+                    .map(|component| component.to_string())
+                    .collect::<Vec<_>>()
+                    .join("/")
+            ),
+            RequirePath::String(path) => write!(f, "{path}"),
+        }
+    }
+}
+
+/// Converts the argument of a require call into a `RequirePath`
+fn argument_to_require_path(expression: &Expression) -> Result<RequirePath> {
+    if let Expression::String(token) = expression {
+        let TokenType::StringLiteral { literal, .. } = token.token_type() else {
+            bail!("require expression not supported: string argument is not a string literal")
+        };
+        return Ok(RequirePath::String(literal.to_string()));
+    }
+
+    expression_to_components(expression).map(RequirePath::Instance)
+}
+
+/// Extracts the argument out of a `require(...)` call expression
+fn require_argument(expression: &Expression) -> Result<&Expression> {
     let Expression::FunctionCall(call) = expression else {
         bail!("'{}' is not a function call", expression.to_string().trim());
     };
@@ -49,7 +155,7 @@ pub fn match_require(expression: &Expression) -> Result<Vec<String>> {
             call.suffixes().next().unwrap()
         {
             if arguments.len() == 1 {
-                return expression_to_components(arguments.iter().next().unwrap());
+                return Ok(arguments.iter().next().unwrap());
             }
         }
     } else {
@@ -62,6 +168,159 @@ pub fn match_require(expression: &Expression) -> Result<Vec<String>> {
     )
 }
 
+/// Extracts the path out of a `require(...)` call expression
+pub fn match_require(expression: &Expression) -> Result<RequirePath> {
+    argument_to_require_path(require_argument(expression)?)
+}
+
+const LUAU_KEYWORDS: &[&str] = &[
+    "and", "break", "continue", "do", "else", "elseif", "end", "false", "for", "function", "if",
+    "in", "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Whether a name can be used as an identifier, e.g. in `script.Name` rather than `script["Name"]`
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+        && !LUAU_KEYWORDS.contains(&name)
+}
+
+/// The steps of a require path relative to the module containing it, e.g. `["Parent", "Example"]` for both
+/// `script.Parent.Example` and `"./Example"`. `None` for paths starting at `game`, which are the same from any module
+fn relative_components(require_path: &RequirePath) -> Result<Option<Vec<PathComponent>>> {
+    let path = match require_path {
+        RequirePath::Instance(components) => {
+            return match components.split_first() {
+                Some((first, rest)) if *first == "script" => Ok(Some(rest.to_vec())),
+                Some((first, _)) if *first == "game" => Ok(None),
+                _ => bail!("require path '{require_path}' does not start with 'script' or 'game'"),
+            };
+        }
+        RequirePath::String(path) => path,
+    };
+
+    // `./` and `../` start from the module's parent, `@self` from the module itself
+    let mut components = Vec::new();
+    let rest = if let Some(rest) = path.strip_prefix("@self") {
+        rest
+    } else if path.starts_with("./") || path.starts_with("../") {
+        components.push(PathComponent::Child("Parent".to_string()));
+        path
+    } else {
+        bail!("require path '{path}' uses an alias, which cannot be followed from another module");
+    };
+
+    for segment in rest.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => components.push(PathComponent::Child("Parent".to_string())),
+            name => components.push(PathComponent::Child(name.to_string())),
+        }
+    }
+
+    Ok(Some(components))
+}
+
+/// Appends a component to an instance path, e.g. `.Example`, `["My Module"]` or `:FindFirstAncestor("Example")`
+fn push_instance_component(argument: &mut String, component: &PathComponent) {
+    match component {
+        PathComponent::Child(name) if is_identifier(name) => argument.push_str(&format!(".{name}")),
+        PathComponent::Child(name) => argument.push_str(&format!("[{name:?}]")),
+        PathComponent::Ancestor(name) => {
+            argument.push_str(&format!(":FindFirstAncestor({name:?})"))
+        }
+    }
+}
+
+/// Creates a `require(...)` call expression for the module reached by following `hops` from the module required by
+/// `expression`, where each hop is the require path in the module reached by the previous one. E.g.
+/// `require(script.Parent.Example)` and `script.Main` become `require(script.Parent.Example.Main)`
+pub fn chained_require(expression: &Expression, hops: &[RequirePath]) -> Result<Expression> {
+    let argument = require_argument(expression)?;
+    let mut base = argument.to_string().trim().to_string();
+    let mut base_path = argument_to_require_path(argument)?;
+
+    // The components to append to the base, where a child's `Parent` cancels out
+    let mut appended: Vec<PathComponent> = Vec::new();
+    for hop in hops {
+        let Some(components) = relative_components(hop)? else {
+            let RequirePath::Instance(components) = hop else {
+                unreachable!()
+            };
+            // Services are the top-level children of the DataModel
+            base = String::from("game");
+            for (index, component) in components.iter().enumerate().skip(1) {
+                match component {
+                    PathComponent::Child(name) if index == 1 => {
+                        base.push_str(&format!(":GetService({name:?})"))
+                    }
+                    component => push_instance_component(&mut base, component),
+                }
+            }
+            base_path = hop.clone();
+            appended.clear();
+            continue;
+        };
+
+        for component in components {
+            match (&component, appended.last()) {
+                (PathComponent::Child(parent), Some(PathComponent::Child(name)))
+                    if parent == "Parent" && name != "Parent" =>
+                {
+                    appended.pop();
+                }
+                _ => appended.push(component),
+            }
+        }
+    }
+
+    let new_argument = match base_path {
+        RequirePath::Instance(_) => {
+            for component in &appended {
+                push_instance_component(&mut base, component);
+            }
+            base
+        }
+        RequirePath::String(path) => {
+            let mut segments: Vec<String> = path.split('/').map(String::from).collect();
+            for component in &appended {
+                match component {
+                    PathComponent::Child(name) if name == "Parent" => {
+                        match segments.last().map(String::as_str) {
+                            Some(".") => *segments.last_mut().unwrap() = "..".to_string(),
+                            Some("..") | None => segments.push("..".to_string()),
+                            Some(segment) if segment.starts_with('@') => {
+                                bail!("cannot require the parent of '{path}'")
+                            }
+                            Some(_) => {
+                                segments.pop();
+                            }
+                        }
+                    }
+                    PathComponent::Child(name) => segments.push(name.clone()),
+                    PathComponent::Ancestor(name) => {
+                        bail!("cannot require ancestor '{name}' of '{path}' with a string require")
+                    }
+                }
+            }
+            format!("{:?}", segments.join("/"))
+        }
+    };
+
+    let code = format!("return require({new_argument})\n");
+    let parsed_code = match full_moon::parse(&code) {
+        Ok(parsed_code) => parsed_code,
+        Err(_) => bail!("could not create require expression '{}'", code.trim()),
+    };
+    match parsed_code.nodes().last_stmt() {
+        Some(LastStmt::Return(r#return)) => Ok(r#return.returns().iter().next().unwrap().clone()),
+        _ => unreachable!(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use full_moon::ast::Stmt;
@@ -78,7 +337,13 @@ mod tests {
     }
 
     fn expression_into_components(code: &str, components: Vec<&str>) -> bool {
-        match_require(&require_expression(code)).unwrap() == components
+        match_require(&require_expression(code)).unwrap()
+            == RequirePath::Instance(
+                components
+                    .into_iter()
+                    .map(|name| PathComponent::Child(name.to_string()))
+                    .collect(),
+            )
     }
 
     #[test]
@@ -97,8 +362,115 @@ mod tests {
         ))
     }
 
+    #[test]
+    fn require_with_get_service() {
+        assert!(expression_into_components(
+            "require(game:GetService('ReplicatedStorage').Packages.Example)",
+            vec!["game", "ReplicatedStorage", "Packages", "Example"]
+        ))
+    }
+
+    #[test]
+    fn get_service_only_allowed_on_game() {
+        assert!(match_require(&require_expression(
+            "require(script:GetService('ReplicatedStorage').Example)"
+        ))
+        .is_err())
+    }
+
+    #[test]
+    fn require_with_child_method_calls() {
+        assert!(expression_into_components(
+            "require(script.Parent:WaitForChild('_Index'):FindFirstChild('pkg'))",
+            vec!["script", "Parent", "_Index", "pkg"]
+        ))
+    }
+
+    #[test]
+    fn require_with_find_first_ancestor() {
+        assert_eq!(
+            match_require(&require_expression(
+                "require(script:FindFirstAncestor('Packages').Example)"
+            ))
+            .unwrap(),
+            RequirePath::Instance(vec![
+                PathComponent::Child("script".to_string()),
+                PathComponent::Ancestor("Packages".to_string()),
+                PathComponent::Child("Example".to_string()),
+            ])
+        )
+    }
+
+    #[test]
+    fn string_require() {
+        assert_eq!(
+            match_require(&require_expression("require('@pkg/Example')")).unwrap(),
+            RequirePath::String("@pkg/Example".to_string())
+        )
+    }
+
     #[test]
     fn unhandled_require() {
-        assert!(match_require(&require_expression("require('string')")).is_err())
+        assert!(match_require(&require_expression("require(getModule())")).is_err())
+    }
+
+    #[test]
+    fn chains_requires() {
+        let chained_require = |code: &str, hops: &[&str]| {
+            let hops: Vec<RequirePath> = hops
+                .iter()
+                .map(|hop| match_require(&require_expression(hop)).unwrap())
+                .collect();
+            chained_require(&require_expression(code), &hops)
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(
+            chained_require(
+                "require(script.Parent._Index['pkg']['pkg'])",
+                &["require(script.Main)"]
+            ),
+            "require(script.Parent._Index['pkg']['pkg'].Main)\n"
+        );
+        assert_eq!(
+            chained_require(
+                "require(script.Parent.Example)",
+                &["require(script.Src['My Module'])"]
+            ),
+            "require(script.Parent.Example.Src[\"My Module\"])\n"
+        );
+        assert_eq!(
+            chained_require("require('./_Index/pkg/pkg')", &["require(script.Main)"]),
+            "require(\"./_Index/pkg/pkg/Main\")\n"
+        );
+        // A link to a sibling link file, which links to another package
+        assert_eq!(
+            chained_require(
+                "require(script.Parent._Index['x_foo@1.0.0']['foo'])",
+                &[
+                    "require(script.Parent.Bar)",
+                    "require(script.Parent.Parent['y_bar@1.0.0']['bar'])"
+                ]
+            ),
+            "require(script.Parent._Index['x_foo@1.0.0']['foo'].Parent.Parent[\"y_bar@1.0.0\"].bar)\n"
+        );
+        assert_eq!(
+            chained_require(
+                "require('./_Index/x_foo@1.0.0/foo')",
+                &["require('./Bar')", "require('@self/Main')"]
+            ),
+            "require(\"./_Index/x_foo@1.0.0/Bar/Main\")\n"
+        );
+        assert_eq!(
+            chained_require(
+                "require(script.Parent.Example)",
+                &[
+                    "require(game:GetService('ReplicatedStorage').Shared)",
+                    "require(script.Main)"
+                ]
+            ),
+            "require(game:GetService(\"ReplicatedStorage\").Shared.Main)\n"
+        );
     }
 }
@@ -0,0 +1,120 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use log::{error, info};
+
+use crate::fixer::*;
+use crate::link_mutator::*;
+use crate::require_parser::*;
+
+fn revert(path: &Path, dry_run: bool, result: &mut ThunkResult) -> Result<ThunkOutcome> {
+    let link_contents = std::fs::read_to_string(path)?;
+    let parsed_code = match full_moon::parse(&link_contents) {
+        Ok(parsed_code) => parsed_code,
+        Err(errors) => bail!(errors
+            .iter()
+            .map(|err| err.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")),
+    };
+
+    let Some(returns) = generated_link_require(parsed_code.nodes()) else {
+        info!(
+            "Link file '{}' was not generated, leaving unchanged",
+            path.display()
+        );
+        return Ok(ThunkOutcome::Unchanged);
+    };
+    result.require_path = returns
+        .iter()
+        .next()
+        .and_then(|expression| match_require(expression).ok());
+
+    let new_link_contents = restore_link(parsed_code, returns).to_string();
+    result.change = Some(LinkChange {
+        path: path.to_path_buf(),
+        old_contents: link_contents,
+        new_contents: new_link_contents.clone(),
+    });
+
+    if dry_run {
+        info!("Link file '{}' would be reverted", path.display());
+    } else {
+        std::fs::write(path, new_link_contents)?;
+        info!("Reverted link file '{}'", path.display());
+    }
+
+    Ok(ThunkOutcome::Successful)
+}
+
+/// Restores a link file generated by this tool to the plain `return require(...)` form generated by wally.
+/// Link files which were not generated are left unchanged
+pub fn revert_thunk(path: &Path, dry_run: bool) -> ThunkResult {
+    let mut result = ThunkResult {
+        path: path.to_path_buf(),
+        require_path: None,
+        linked_file: None,
+        exported_types: Vec::new(),
+        change: None,
+        outcome: ThunkOutcome::Unchanged,
+    };
+
+    result.outcome = match revert(path, dry_run, &mut result) {
+        Ok(outcome) => outcome,
+        Err(err) => {
+            error!("Failed to revert '{}': {:#}", path.display(), err);
+            ThunkOutcome::Error(err)
+        }
+    };
+
+    result
+}
+
+/// Restores all link files in a packages folder, including the ones inside its `_Index`
+pub fn revert_packages_folder(path: &Path, dry_run: bool) -> Result<PackagesFolderResult> {
+    let (thunks, complete) = find_thunks(path)?;
+
+    Ok(PackagesFolderResult {
+        path: path.to_path_buf(),
+        complete,
+        thunks: thunks
+            .iter()
+            .map(|thunk| revert_thunk(thunk, dry_run))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn reverts_generated_links() {
+        let root = TempDir::new("revert");
+
+        let generated = root.join("Generated.lua");
+        std::fs::write(
+            &generated,
+            "--!strict\nlocal REQUIRED_MODULE = require(script.Parent._Index['pkg']['pkg'])\nlocal REQUIRED_TYPES = require(script.Parent._Index['pkg']['pkg'].Main)\nexport type Value = REQUIRED_TYPES.Value\nreturn REQUIRED_MODULE\n",
+        )
+        .unwrap();
+        let plain = root.join("Plain.lua");
+        std::fs::write(
+            &plain,
+            "return require(script.Parent._Index['pkg']['pkg'])\n",
+        )
+        .unwrap();
+
+        let result = revert_packages_folder(&root, false).unwrap();
+        assert!(result.is_success());
+        assert_eq!(
+            std::fs::read_to_string(&generated).unwrap(),
+            "--!strict\nreturn require(script.Parent._Index['pkg']['pkg'])\n"
+        );
+        assert!(matches!(
+            revert_thunk(&plain, false).outcome,
+            ThunkOutcome::Unchanged
+        ));
+    }
+}
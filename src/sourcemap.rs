@@ -1,7 +1,9 @@
 use anyhow::{Context, Result};
 use serde::Deserialize;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
 
+/// A node of a Rojo sourcemap, as generated by `rojo sourcemap`
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct SourcemapNode {
@@ -13,26 +15,246 @@ pub struct SourcemapNode {
     pub children: Vec<SourcemapNode>,
 }
 
-impl SourcemapNode {
-    pub fn find_child(&self, name: String) -> Option<&SourcemapNode> {
-        self.children.iter().find(|child| child.name == name)
+/// Identifies a node within a `SourcemapIndex`
+pub type NodeId = usize;
+
+/// A flattened view of the sourcemap tree, built once so nodes can be looked up by canonical file path
+/// and navigated through parent links without searching the whole tree
+pub struct SourcemapIndex<'a> {
+    nodes: Vec<&'a SourcemapNode>,
+    parents: Vec<Option<NodeId>>,
+    children: Vec<Vec<NodeId>>,
+    file_paths: HashMap<&'a Path, NodeId>,
+}
+
+impl<'a> SourcemapIndex<'a> {
+    pub fn new(root: &'a SourcemapNode) -> Self {
+        let mut index = SourcemapIndex {
+            nodes: Vec::new(),
+            parents: Vec::new(),
+            children: Vec::new(),
+            file_paths: HashMap::new(),
+        };
+
+        let mut stack = vec![(root, None)];
+        while let Some((node, parent)) = stack.pop() {
+            let id = index.nodes.len();
+            index.nodes.push(node);
+            index.parents.push(parent);
+            index.children.push(Vec::new());
+
+            if let Some(parent) = parent {
+                index.children[parent].push(id);
+            }
+
+            for file_path in &node.file_paths {
+                // A file path should only belong to a single node, but keep the first one found if not
+                index.file_paths.entry(file_path.as_path()).or_insert(id);
+            }
+
+            // Pushed in reverse so children are visited (and stored) in their original order
+            for child in node.children.iter().rev() {
+                stack.push((child, Some(id)));
+            }
+        }
+
+        index
+    }
+
+    pub fn root(&self) -> NodeId {
+        0
+    }
+
+    pub fn node(&self, id: NodeId) -> &'a SourcemapNode {
+        self.nodes[id]
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.parents[id]
     }
+
+    /// Finds the node which has the given file path. Paths are compared lexically first, so that the file system is
+    /// only accessed to resolve symlinks when that fails
+    pub fn find_by_path(&self, path: &Path) -> Option<NodeId> {
+        if let Some(id) = std::path::absolute(path)
+            .ok()
+            .and_then(|path| self.file_paths.get(normalize_path(&path).as_path()))
+        {
+            return Some(*id);
+        }
+
+        let canonical_path = path.canonicalize().ok()?;
+        if let Some(id) = self.file_paths.get(canonical_path.as_path()) {
+            return Some(*id);
+        }
+
+        // The sourcemap path itself may contain a symlink, so only resolve the paths of files with the same name
+        self.file_paths
+            .iter()
+            .filter(|(file_path, _)| file_path.file_name() == canonical_path.file_name())
+            .find(|(file_path, _)| file_path.canonicalize().ok().as_ref() == Some(&canonical_path))
+            .map(|(_, id)| *id)
+    }
+
+    pub fn find_child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        self.children[id]
+            .iter()
+            .copied()
+            .find(|child| self.nodes[*child].name == name)
+    }
+
+    /// Returns the names of all nodes from the root down to the given node, joined by `/`
+    pub fn full_name(&self, id: NodeId) -> String {
+        let mut names = vec![self.nodes[id].name.as_str()];
+        let mut current = id;
+        while let Some(parent) = self.parents[current] {
+            names.push(self.nodes[parent].name.as_str());
+            current = parent;
+        }
+
+        names.reverse();
+        names.join("/")
+    }
+}
+
+/// Normalizes a path lexically, removing `.` components and resolving `..` components without accessing the file system
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
 }
 
-/// Updates all file paths in the sourcemap into canonical form, to allow matching later
-pub fn mutate_sourcemap(node: &mut SourcemapNode) -> Result<()> {
-    node.file_paths = node
-        .file_paths
-        .iter()
-        .map(|path| {
-            path.canonicalize()
-                .context(format!("Failed to canonicalize '{}'", path.display()))
-        })
-        .collect::<Result<Vec<_>, _>>()?;
+fn normalize_sourcemap_paths(node: &mut SourcemapNode, project_root: &Path) {
+    for path in &mut node.file_paths {
+        *path = normalize_path(&project_root.join(&*path));
+    }
 
     for child in &mut node.children {
-        mutate_sourcemap(child)?;
+        normalize_sourcemap_paths(child, project_root);
     }
+}
+
+/// Updates all file paths in the sourcemap into absolute, normalized form, to allow matching later.
+/// Relative paths are resolved against `project_root`, usually the directory of the sourcemap.
+/// Only the project root is canonicalized: file paths are normalized lexically, and symlinks or missing files are
+/// only dealt with when a link file needs them, so large sourcemaps load without touching the file system
+pub fn mutate_sourcemap(node: &mut SourcemapNode, project_root: &Path) -> Result<()> {
+    let project_root = project_root.canonicalize().with_context(|| {
+        format!(
+            "Failed to canonicalize project root '{}'",
+            project_root.display()
+        )
+    })?;
+    normalize_sourcemap_paths(node, &project_root);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn indexes_nodes_by_path_with_parent_links() {
+        let root: SourcemapNode = serde_json::from_str(
+            r#"{
+                "name": "Game",
+                "className": "DataModel",
+                "children": [
+                    {
+                        "name": "Packages",
+                        "className": "Folder",
+                        "children": [
+                            { "name": "First", "className": "ModuleScript", "filePaths": ["/project/First.lua"] },
+                            { "name": "Second", "className": "ModuleScript", "filePaths": ["/project/Second.lua"] }
+                        ]
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let index = SourcemapIndex::new(&root);
+
+        let second = index
+            .find_by_path(Path::new("/project/Packages/../Second.lua"))
+            .unwrap();
+        assert_eq!(index.node(second).name, "Second");
+        assert_eq!(index.full_name(second), "Game/Packages/Second");
+
+        let packages = index.parent(second).unwrap();
+        assert_eq!(
+            index
+                .find_child(packages, "First")
+                .map(|id| index.node(id).name.as_str()),
+            Some("First")
+        );
+        assert_eq!(index.parent(packages), Some(index.root()));
+        assert_eq!(index.parent(index.root()), None);
+    }
+
+    #[test]
+    fn normalizes_paths_lexically() {
+        assert_eq!(
+            normalize_path(Path::new("/project/./Packages/../Packages/_Index")),
+            PathBuf::from("/project/Packages/_Index")
+        );
+        assert_eq!(
+            normalize_path(Path::new("../a/../../b")),
+            PathBuf::from("../../b")
+        );
+    }
+
+    #[test]
+    fn resolves_relative_paths_without_touching_files() {
+        let root = TempDir::new("sourcemap");
+        std::fs::create_dir_all(root.join("Packages")).unwrap();
+        std::fs::write(root.join("Packages/Example.lua"), "").unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let mut sourcemap: SourcemapNode = serde_json::from_str(
+            r#"{
+                "name": "Packages",
+                "className": "Folder",
+                "filePaths": ["Packages"],
+                "children": [
+                    { "name": "Example", "className": "ModuleScript", "filePaths": ["./Packages/Example.lua"] },
+                    { "name": "Missing", "className": "ModuleScript", "filePaths": ["Packages/Missing.lua"] }
+                ]
+            }"#,
+        )
+        .unwrap();
+        mutate_sourcemap(&mut sourcemap, &root).unwrap();
+
+        assert_eq!(
+            sourcemap.children[0].file_paths,
+            vec![root.join("Packages/Example.lua")]
+        );
+        // Missing files are kept, and only fail the link files which need them
+        assert_eq!(
+            sourcemap.children[1].file_paths,
+            vec![root.join("Packages/Missing.lua")]
+        );
+
+        let index = SourcemapIndex::new(&sourcemap);
+        assert_eq!(
+            index
+                .find_by_path(&root.join("Packages/Example.lua"))
+                .map(|id| index.node(id).name.as_str()),
+            Some("Example")
+        );
+    }
+}
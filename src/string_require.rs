@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::sourcemap::normalize_path;
+
+#[derive(Deserialize, Debug, Default)]
+struct LuauRc {
+    #[serde(default)]
+    aliases: HashMap<String, PathBuf>,
+}
+
+/// Splits a require string of the form `@alias/rest` into its alias and the remaining path
+fn split_alias(require: &str) -> Option<(&str, &str)> {
+    let aliased = require.strip_prefix('@')?;
+    Some(aliased.split_once('/').unwrap_or((aliased, "")))
+}
+
+/// Finds the directory an alias points to, searching `.luaurc` files from `directory` upwards
+fn find_alias(directory: &Path, alias: &str) -> Result<Option<PathBuf>> {
+    for ancestor in directory.ancestors() {
+        let luaurc_path = ancestor.join(".luaurc");
+        if !luaurc_path.is_file() {
+            continue;
+        }
+
+        let luaurc: LuauRc = serde_json::from_str(
+            &std::fs::read_to_string(&luaurc_path).context("Failed to read .luaurc file")?,
+        )
+        .with_context(|| format!("Failed to parse '{}'", luaurc_path.display()))?;
+
+        // Aliases are case-insensitive
+        if let Some((_, alias_path)) = luaurc
+            .aliases
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(alias))
+        {
+            return Ok(Some(ancestor.join(alias_path)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Resolves a module path without extension to a file, e.g. `Example` to `Example.luau` or `Example/init.luau`
+fn resolve_module_file(path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?.to_string_lossy();
+
+    [
+        path.with_file_name(format!("{file_name}.luau")),
+        path.with_file_name(format!("{file_name}.lua")),
+        path.join("init.luau"),
+        path.join("init.lua"),
+    ]
+    .into_iter()
+    .find(|candidate| candidate.is_file())
+}
+
+/// Given a Luau string require (e.g., `./Example` or `@pkg/Example`) in the file at `path`, converts it to a file path.
+/// Requires are resolved relative to the module, which is the containing directory for init files: `@self` is the
+/// module itself, and `./` and `../` start from the module's parent
+pub fn file_path_from_string_require(path: &Path, require: &str) -> Result<PathBuf> {
+    let directory = path.parent().context("Link file has no parent directory")?;
+    let module = match path.file_stem() {
+        Some(stem) if stem != "init" => directory.join(stem),
+        _ => directory.to_path_buf(),
+    };
+
+    let module_path = if let Some((alias, rest)) = split_alias(require) {
+        let alias_path = if alias.eq_ignore_ascii_case("self") {
+            module
+        } else {
+            find_alias(directory, alias)?
+                .with_context(|| format!("Alias '@{alias}' not found in any .luaurc file"))?
+        };
+        alias_path.join(rest)
+    } else if require.starts_with("./") || require.starts_with("../") {
+        let parent = module.parent().context("Module has no parent directory")?;
+        normalize_path(&parent.join(require))
+    } else {
+        bail!("require string '{require}' must start with './', '../' or an '@' alias");
+    };
+
+    resolve_module_file(&module_path)
+        .with_context(|| format!("No .lua/.luau file found for '{}'", module_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn splits_aliases() {
+        assert_eq!(split_alias("@pkg/Example"), Some(("pkg", "Example")));
+        assert_eq!(split_alias("@pkg"), Some(("pkg", "")));
+        assert_eq!(split_alias("./Example"), None);
+    }
+
+    #[test]
+    fn resolves_relative_and_aliased_requires() {
+        let root = TempDir::new("string-require");
+        std::fs::create_dir_all(root.join("Packages/_Index/pkg")).unwrap();
+        std::fs::write(root.join("Packages/_Index/pkg/init.luau"), "").unwrap();
+        std::fs::write(root.join("Packages/Example.luau"), "").unwrap();
+        std::fs::write(
+            root.join(".luaurc"),
+            r#"{ "aliases": { "Index": "Packages/_Index" } }"#,
+        )
+        .unwrap();
+
+        let link = root.join("Packages/Link.luau");
+        assert_eq!(
+            file_path_from_string_require(&link, "./_Index/pkg").unwrap(),
+            root.join("Packages/_Index/pkg/init.luau")
+        );
+        assert_eq!(
+            file_path_from_string_require(&link, "@index/pkg").unwrap(),
+            root.join("Packages/_Index/pkg/init.luau")
+        );
+        assert_eq!(
+            file_path_from_string_require(&root.join("Packages/init.luau"), "@self/Example")
+                .unwrap(),
+            root.join("Packages/Example.luau")
+        );
+        // Relative requires in init files start from the directory's parent, like `@self` starts from the directory
+        assert!(file_path_from_string_require(
+            &root.join("Packages/_Index/pkg/init.luau"),
+            "./Example"
+        )
+        .is_err());
+        assert_eq!(
+            file_path_from_string_require(
+                &root.join("Packages/_Index/pkg/init.luau"),
+                "../Example"
+            )
+            .unwrap(),
+            root.join("Packages/Example.luau")
+        );
+        assert!(file_path_from_string_require(&link, "Example").is_err());
+    }
+}
@@ -0,0 +1,42 @@
+use log::{log, Level};
+
+/// Buffers the log messages produced while handling a single link file, so that they stay grouped
+/// when link files are handled concurrently
+#[derive(Default)]
+pub struct ThunkLog {
+    entries: Vec<(Level, String)>,
+}
+
+impl ThunkLog {
+    pub fn log(&mut self, level: Level, message: impl Into<String>) {
+        self.entries.push((level, message.into()));
+    }
+
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.log(Level::Info, message);
+    }
+
+    pub fn warn(&mut self, message: impl Into<String>) {
+        self.log(Level::Warn, message);
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.log(Level::Error, message);
+    }
+
+    /// Logs all buffered messages in the order they were produced
+    pub fn flush(self) {
+        for (level, message) in self.entries {
+            log!(level, "{}", message);
+        }
+    }
+
+    /// The buffered messages logged at `level`
+    #[cfg(test)]
+    pub fn messages(&self, level: Level) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .filter(move |(entry_level, _)| *entry_level == level)
+            .map(|(_, message)| message.as_str())
+    }
+}
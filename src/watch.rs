@@ -0,0 +1,251 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use log::{error, info};
+
+use crate::command::*;
+use crate::fixer::*;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Modification times of every watched file
+type Snapshot = HashMap<PathBuf, SystemTime>;
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Records the modification time of a file, or of every file below it if it is a directory
+fn snapshot_path(path: &Path, snapshot: &mut Snapshot) {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return;
+    };
+
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path).into_iter().flatten().flatten() {
+            snapshot_path(&entry.path(), snapshot);
+        }
+    } else if let Ok(modified) = metadata.modified() {
+        snapshot.insert(path.to_path_buf(), modified);
+    }
+}
+
+/// Returns every file which was added, modified or removed between two snapshots
+fn changed_paths(old: &Snapshot, new: &Snapshot) -> HashSet<PathBuf> {
+    let removed = old.keys().filter(|path| !new.contains_key(*path));
+    let added_or_modified = new
+        .iter()
+        .filter(|(path, modified)| old.get(*path) != Some(modified))
+        .map(|(path, _)| path);
+
+    removed.chain(added_or_modified).cloned().collect()
+}
+
+fn canonicalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// The file each link points to, or None if the link could not be fixed
+type LinkedFiles = HashMap<PathBuf, Option<PathBuf>>;
+
+fn record_linked_files(linked_files: &mut LinkedFiles, results: &[ThunkResult]) {
+    for result in results {
+        linked_files.insert(
+            canonicalize(&result.path),
+            result
+                .linked_file
+                .as_ref()
+                .filter(|_| result.is_success())
+                .map(|linked_file| canonicalize(linked_file)),
+        );
+    }
+}
+
+/// The latest result of every link file, to regenerate the types module from after fixing only some of them
+type ThunkResults = HashMap<PathBuf, ThunkResult>;
+
+fn record_thunk_results(
+    thunk_results: &mut ThunkResults,
+    results: impl IntoIterator<Item = ThunkResult>,
+) {
+    for result in results {
+        thunk_results.insert(canonicalize(&result.path), result);
+    }
+}
+
+impl Command {
+    fn canonical_packages_folders(&self) -> Vec<PathBuf> {
+        self.packages_folders
+            .iter()
+            .map(|path| canonicalize(path))
+            .collect()
+    }
+
+    fn snapshot_packages_folders(&self) -> Snapshot {
+        let mut snapshot = Snapshot::new();
+        for path in self.canonical_packages_folders() {
+            snapshot_path(&path, &mut snapshot);
+        }
+
+        snapshot
+    }
+
+    /// Keeps fixing link files whenever the sourcemap or the packages folders change.
+    /// A changed sourcemap fixes all link files again, otherwise only the affected ones are fixed. A sourcemap built
+    /// from a project is built again on every change
+    pub(crate) fn watch(&self) -> Result<()> {
+        loop {
+            let sourcemap_modified = self.sourcemap_file().and_then(modified);
+            let sourcemap_changed =
+                || self.sourcemap_file().and_then(modified) != sourcemap_modified;
+
+            let sourcemap = match self.load_sourcemap() {
+                Ok(sourcemap) => sourcemap,
+                Err(err) => {
+                    error!("{:#}", err);
+                    info!("Waiting for the sourcemap to change...");
+                    while !sourcemap_changed() {
+                        std::thread::sleep(POLL_INTERVAL);
+                    }
+                    continue;
+                }
+            };
+            let fixer = self.fixer(sourcemap.as_ref());
+
+            let mut linked_files = LinkedFiles::new();
+            let mut thunk_results = ThunkResults::new();
+
+            match self.handle_packages_folders(&fixer) {
+                Ok(results) => {
+                    for result in &results {
+                        record_linked_files(&mut linked_files, &result.thunks);
+                    }
+                    if let Err(err) = self.summarize(&results) {
+                        error!("{:#}", err);
+                    }
+                    record_thunk_results(
+                        &mut thunk_results,
+                        results.into_iter().flat_map(|result| result.thunks),
+                    );
+                }
+                Err(err) => error!("{:#}", err),
+            }
+
+            // Snapshots are taken after fixing, so that written link files are not seen as changes
+            let mut snapshot = self.snapshot_packages_folders();
+            info!("Watching for changes...");
+
+            while !sourcemap_changed() {
+                std::thread::sleep(POLL_INTERVAL);
+
+                let changed = changed_paths(&snapshot, &self.snapshot_packages_folders());
+                if changed.is_empty() {
+                    continue;
+                }
+
+                // A sourcemap built from a project goes stale when packages are added, e.g. by `wally install`,
+                // without the project file changing, so it is built again
+                let project_sourcemap = match &self.project {
+                    Some(_) => match self.load_sourcemap() {
+                        Ok(sourcemap) => sourcemap,
+                        Err(err) => {
+                            error!("{:#}", err);
+                            snapshot = self.snapshot_packages_folders();
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
+                let project_fixer;
+                let fixer = match &project_sourcemap {
+                    Some(sourcemap) => {
+                        project_fixer = self.fixer(Some(sourcemap));
+                        &project_fixer
+                    }
+                    None => &fixer,
+                };
+
+                // Fix link files which are new or changed, point at a changed file, or failed previously
+                let mut thunks = Vec::new();
+                for path in self.canonical_packages_folders() {
+                    let Ok((folder_thunks, _)) = find_thunks(&path) else {
+                        continue;
+                    };
+
+                    thunks.extend(folder_thunks.into_iter().filter(|thunk| {
+                        changed.contains(thunk)
+                            || match linked_files.get(thunk) {
+                                Some(Some(linked_file)) => changed.contains(linked_file),
+                                _ => true,
+                            }
+                    }));
+                }
+
+                if !thunks.is_empty() {
+                    info!("Changes detected, fixing {} link files", thunks.len());
+                    let results = fixer.fix_thunks(&thunks);
+                    let failures = results.iter().filter(|result| !result.is_success()).count();
+                    record_linked_files(&mut linked_files, &results);
+                    self.print_changes(&results);
+
+                    if failures == 0 {
+                        info!("Mutation completed successfully for all changed link files");
+                    } else {
+                        error!(
+                            "Mutation failed for {} out of {} changed link files",
+                            failures,
+                            thunks.len()
+                        );
+                    }
+                    record_thunk_results(&mut thunk_results, results);
+                }
+
+                // The types module aggregates all link files, so it changes with any of them, including removed ones
+                thunk_results.retain(|path, _| path.exists());
+                let all_results: Vec<&ThunkResult> = thunk_results.values().collect();
+                if let Err(err) = self.update_types_module(&all_results) {
+                    error!("{:#}", err);
+                }
+
+                snapshot = self.snapshot_packages_folders();
+            }
+
+            info!("Sourcemap changed, fixing all link files");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_added_modified_and_removed_files() {
+        let now = SystemTime::now();
+        let later = now + Duration::from_secs(1);
+
+        let old = Snapshot::from([
+            (PathBuf::from("kept"), now),
+            (PathBuf::from("modified"), now),
+            (PathBuf::from("removed"), now),
+        ]);
+        let new = Snapshot::from([
+            (PathBuf::from("kept"), now),
+            (PathBuf::from("modified"), later),
+            (PathBuf::from("added"), now),
+        ]);
+
+        assert_eq!(
+            changed_paths(&old, &new),
+            HashSet::from([
+                PathBuf::from("modified"),
+                PathBuf::from("removed"),
+                PathBuf::from("added")
+            ])
+        );
+    }
+}